@@ -1,12 +1,30 @@
+use crate::camera::MainCamera;
+use crate::player::Player;
 use crate::GameState;
+use bevy::input::gamepad::Gamepads;
+use bevy::input::touch::Touch;
 use bevy::prelude::*;
 
+/// Turn rate in radians per second with the steering fully to one side
+pub const MAX_STEERING: f32 = 1.2;
+/// Stick deflection below this counts as centered
+const STICK_DEAD_ZONE: f32 = 0.15;
+/// Steering per radian between the walking direction and the pointer
+const POINTER_STEERING: f32 = 3.;
+
 pub struct ActionsPlugin;
 
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(set_movement_actions),
+            SystemSet::on_update(GameState::Playing)
+                .with_system(set_movement_actions.label(ActionsLabels::Keyboard))
+                .with_system(
+                    set_gamepad_actions
+                        .label(ActionsLabels::Gamepad)
+                        .after(ActionsLabels::Keyboard),
+                )
+                .with_system(set_pointer_actions.after(ActionsLabels::Gamepad)),
         )
         .add_system_set(
             SystemSet::on_enter(GameState::Playing).with_system(initialize.exclusive_system()),
@@ -21,23 +39,29 @@ fn initialize(world: &mut World) {
 
 pub struct Actions {
     pub steering: Option<f32>,
+    pub tiptoe: bool,
+    pub sprint: bool,
+    /// Only set in the frame the jump key went down
+    pub jump: bool,
 }
 
 impl FromWorld for Actions {
     fn from_world(world: &mut World) -> Self {
         let input = world.get_resource::<Input<KeyCode>>().unwrap();
-        if GameControl::Left.pressed(input) && GameControl::Right.pressed(input) {
-            Actions { steering: None }
+        let steering = if GameControl::Left.pressed(input) && GameControl::Right.pressed(input) {
+            None
         } else if GameControl::Left.pressed(input) {
-            Actions {
-                steering: Some(-1.2),
-            }
+            Some(-MAX_STEERING)
         } else if GameControl::Right.pressed(input) {
-            Actions {
-                steering: Some(1.2),
-            }
+            Some(MAX_STEERING)
         } else {
-            Actions { steering: None }
+            None
+        };
+        Actions {
+            steering,
+            tiptoe: GameControl::Tiptoe.pressed(input),
+            sprint: GameControl::Sprint.pressed(input),
+            jump: false,
         }
     }
 }
@@ -54,16 +78,16 @@ fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<
             || GameControl::Left.just_released(&keyboard_input)
         {
             if GameControl::Right.pressed(&keyboard_input) {
-                steering = 1.2;
+                steering = MAX_STEERING;
             } else if GameControl::Left.pressed(&keyboard_input) {
-                steering = -1.2;
+                steering = -MAX_STEERING;
             } else {
                 steering = 0.;
             }
         } else if GameControl::Right.just_pressed(&keyboard_input) {
-            steering = 1.2;
+            steering = MAX_STEERING;
         } else if GameControl::Left.just_pressed(&keyboard_input) {
-            steering = -1.2;
+            steering = -MAX_STEERING;
         } else {
             steering = actions.steering.unwrap_or(0.);
         }
@@ -71,11 +95,119 @@ fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<
     } else {
         actions.steering = None;
     }
+    actions.tiptoe = GameControl::Tiptoe.pressed(&keyboard_input);
+    actions.sprint = GameControl::Sprint.pressed(&keyboard_input);
+    actions.jump = GameControl::Jump.just_pressed(&keyboard_input);
+}
+
+/// The left stick steers proportionally, the D-pad and shoulder buttons steer like the keys
+/// Triggers or D-pad down and up tiptoe and sprint; the south button jumps.
+fn set_gamepad_actions(
+    mut actions: ResMut<Actions>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<Input<GamepadButton>>,
+) {
+    for &gamepad in gamepads.iter() {
+        let pressed = |button_type| buttons.pressed(GamepadButton(gamepad, button_type));
+        // keys win, so a resting stick does not cancel steering with the keyboard
+        if actions.steering.is_none() {
+            let stick = axes
+                .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or(0.);
+            let left =
+                pressed(GamepadButtonType::DPadLeft) || pressed(GamepadButtonType::LeftTrigger);
+            let right =
+                pressed(GamepadButtonType::DPadRight) || pressed(GamepadButtonType::RightTrigger);
+            actions.steering = if stick.abs() > STICK_DEAD_ZONE {
+                Some(stick.clamp(-1., 1.) * MAX_STEERING)
+            } else if left && !right {
+                Some(-MAX_STEERING)
+            } else if right && !left {
+                Some(MAX_STEERING)
+            } else {
+                None
+            };
+        }
+        actions.tiptoe |=
+            pressed(GamepadButtonType::DPadDown) || pressed(GamepadButtonType::LeftTrigger2);
+        actions.sprint |=
+            pressed(GamepadButtonType::DPadUp) || pressed(GamepadButtonType::RightTrigger2);
+        actions.jump |= buttons.just_pressed(GamepadButton(gamepad, GamepadButtonType::South));
+    }
+}
+
+/// While the left mouse button is held or the screen is touched, the player turns toward the pointer
+/// Pointing at a button, e.g. to press "Try again!", doesn't steer.
+fn set_pointer_actions(
+    mut actions: ResMut<Actions>,
+    windows: Res<Windows>,
+    mouse_input: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    camera: Query<&Transform, With<MainCamera>>,
+    player: Query<&Transform, (With<Player>, Without<MainCamera>)>,
+    ui_nodes: Query<&Interaction>,
+) {
+    if actions.steering.is_some()
+        || ui_nodes
+            .iter()
+            .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+    let (window, camera, player) = match (
+        windows.get_primary(),
+        camera.get_single(),
+        player.get_single(),
+    ) {
+        (Some(window), Ok(camera), Ok(player)) => (window, camera, player),
+        _ => return,
+    };
+    let pointer = if let Some(touch) = touches.iter().next() {
+        touch_position(touch, window)
+    } else if mouse_input.pressed(MouseButton::Left) {
+        match window.cursor_position() {
+            Some(position) => position,
+            None => return,
+        }
+    } else {
+        return;
+    };
+    let target = pointer - Vec2::new(window.width(), window.height()) / 2.
+        + camera.translation.truncate()
+        - player.translation.truncate();
+    if target.length() < 1. {
+        return;
+    }
+    let forward = player.rotation.mul_vec3(Vec3::Y).truncate();
+    // positive steering turns clockwise
+    let angle = -forward.angle_between(target);
+    actions.steering = Some((angle * POINTER_STEERING).clamp(-MAX_STEERING, MAX_STEERING));
+}
+
+/// Touch position counted from the bottom of the window, like the cursor position
+/// bevy_winit only flips touches on Android and iOS; everywhere else they count from the top.
+fn touch_position(touch: &Touch, window: &Window) -> Vec2 {
+    let position = touch.position();
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        position
+    } else {
+        Vec2::new(position.x, window.height() - position.y)
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+enum ActionsLabels {
+    Keyboard,
+    Gamepad,
 }
 
 enum GameControl {
     Left,
     Right,
+    Tiptoe,
+    Sprint,
+    Jump,
 }
 
 impl GameControl {
@@ -89,6 +221,15 @@ impl GameControl {
                 keyboard_input.just_released(KeyCode::D)
                     || keyboard_input.just_released(KeyCode::Right)
             }
+            GameControl::Tiptoe => {
+                keyboard_input.just_released(KeyCode::S)
+                    || keyboard_input.just_released(KeyCode::Down)
+            }
+            GameControl::Sprint => {
+                keyboard_input.just_released(KeyCode::W)
+                    || keyboard_input.just_released(KeyCode::Up)
+            }
+            GameControl::Jump => keyboard_input.just_released(KeyCode::Space),
         }
     }
 
@@ -100,6 +241,13 @@ impl GameControl {
             GameControl::Right => {
                 keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right)
             }
+            GameControl::Tiptoe => {
+                keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down)
+            }
+            GameControl::Sprint => {
+                keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up)
+            }
+            GameControl::Jump => keyboard_input.pressed(KeyCode::Space),
         }
     }
 
@@ -113,6 +261,14 @@ impl GameControl {
                 keyboard_input.just_pressed(KeyCode::D)
                     || keyboard_input.just_pressed(KeyCode::Right)
             }
+            GameControl::Tiptoe => {
+                keyboard_input.just_pressed(KeyCode::S)
+                    || keyboard_input.just_pressed(KeyCode::Down)
+            }
+            GameControl::Sprint => {
+                keyboard_input.just_pressed(KeyCode::W) || keyboard_input.just_pressed(KeyCode::Up)
+            }
+            GameControl::Jump => keyboard_input.just_pressed(KeyCode::Space),
         }
     }
 }
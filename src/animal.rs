@@ -1,13 +1,20 @@
 use crate::animate::{AnimationTimer, Falling};
+use crate::difficulty::Difficulty;
+use crate::floe::Adrift;
 use crate::ice::{
     get_random_direction, get_random_point, get_random_spawn_point, IceLabels, SpawnPoints,
 };
+use crate::level::{AnimalWave, Arena, CurrentLevel};
 use crate::loading::TextureAssets;
-use crate::player::{AnimalFallEvent, Drowning};
-use crate::{GameState, Level, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::player::{AnimalFallEvent, Drowning, PlayerLabels};
+use crate::rng::GameRng;
+use crate::simulation::IceSimulation;
+use crate::{GameState, Level};
 use bevy::math::Mat2;
 use bevy::prelude::*;
-use rand::random;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub struct AnimalPlugin;
 
@@ -16,51 +23,130 @@ pub const ANIMAL_Z: f32 = 4.;
 impl Plugin for AnimalPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
-            SystemSet::on_enter(GameState::Countdown).with_system(spawn_initial_animals),
+            SystemSet::on_enter(GameState::Countdown)
+                .with_system(spawn_initial_animals.after(PlayerLabels::SpawnPlayer)),
         )
         .add_system_set(
             SystemSet::on_update(GameState::Playing)
-                .with_system(move_animals.before(IceLabels::CheckIceGrid))
+                .with_system(
+                    move_animals
+                        .label(AnimalLabels::MoveAnimals)
+                        .before(IceLabels::CheckIceGrid),
+                )
                 .with_system(drown_animals.after(IceLabels::CheckIceGrid))
-                .with_system(spawn_animals),
+                .with_system(spawn_animals.after(IceLabels::BreakIce)),
         );
     }
 }
 
-#[derive(Component)]
-struct SpawnAnimalTimer(Timer);
+/// The waves of the current level still to come; the last one repeats
+pub struct AnimalWaves {
+    waves: Vec<AnimalWave>,
+    current: usize,
+    timer: Timer,
+}
+
+impl AnimalWaves {
+    fn new(waves: Vec<AnimalWave>) -> Self {
+        let delay = waves.first().map(|wave| wave.delay).unwrap_or(0.);
+        AnimalWaves {
+            waves,
+            current: 0,
+            timer: Timer::from_seconds(delay, false),
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Seconds since the previous wave
+    pub fn elapsed_secs(&self) -> f32 {
+        self.timer.elapsed_secs()
+    }
+
+    /// Continue waiting for wave `current`, `elapsed` seconds after the previous one
+    pub fn restore(&mut self, current: usize, elapsed: f32) {
+        if self.waves.is_empty() {
+            return;
+        }
+        self.current = current.min(self.waves.len() - 1);
+        self.timer = Timer::from_seconds(self.waves[self.current].delay, false);
+        self.timer.set_elapsed(Duration::from_secs_f32(elapsed));
+    }
+
+    /// Number of animals to spawn now
+    fn tick(&mut self, delta: Duration) -> usize {
+        if self.waves.is_empty() {
+            return 0;
+        }
+        self.timer.tick(delta);
+        if !self.timer.just_finished() {
+            return 0;
+        }
+        let count = self.waves[self.current].count;
+        self.current = (self.current + 1).min(self.waves.len() - 1);
+        self.timer = Timer::from_seconds(self.waves[self.current].delay, false);
+
+        count
+    }
+}
 
 fn spawn_animals(
     mut commands: Commands,
-    mut timer: ResMut<SpawnAnimalTimer>,
+    mut waves: ResMut<AnimalWaves>,
     time: Res<Time>,
+    difficulty: Res<Difficulty>,
     textures: Res<TextureAssets>,
+    arena: Res<Arena>,
+    simulation: Res<IceSimulation>,
+    mut rng: ResMut<GameRng>,
 ) {
-    timer.0.tick(time.delta());
-    if !timer.0.just_finished() {
-        return;
-    }
-
-    let random_spawn_point = get_random_point(100.);
-    let random_direction = get_random_direction();
-    let mut transform = Transform::from_translation(Vec3::new(
-        random_spawn_point.x,
-        random_spawn_point.y,
-        ANIMAL_Z,
-    ));
-    transform.rotation = Quat::from_rotation_z(-random_direction.angle_between(Vec2::new(0., 1.)));
+    for _ in 0..waves.tick(time.delta().mul_f32(difficulty.spawn_rate.max(0.))) {
+        let random_spawn_point = match get_random_point(&arena, &simulation, 100., &mut rng.0) {
+            Some(point) => point,
+            None => continue,
+        };
+        let random_direction = get_random_direction(&mut rng.0);
+        let species = Species::random(&mut rng.0);
+        let animal = spawn_animal(
+            &mut commands,
+            &textures,
+            species,
+            random_spawn_point,
+            random_direction,
+        );
+        commands
+            .entity(animal)
+            .insert(Falling)
+            .insert(AnimationTimer(Timer::from_seconds(2., false)));
+    }
+}
+
+pub fn spawn_animal(
+    commands: &mut Commands,
+    textures: &TextureAssets,
+    species: Species,
+    position: Vec2,
+    direction: Vec2,
+) -> Entity {
+    let mut transform = Transform::from_translation(position.extend(ANIMAL_Z));
+    transform.rotation = Quat::from_rotation_z(-direction.angle_between(Vec2::new(0., 1.)));
     commands
         .spawn_bundle(SpriteBundle {
             texture: textures.animal.clone(),
+            sprite: species.sprite(),
             transform,
             ..Default::default()
         })
         .insert(Level)
         .insert(Animal)
-        .insert(Falling)
-        .insert(AnimationTimer(Timer::from_seconds(2., false)))
-        .insert(Walking(random_direction))
-        .insert(Steering(None));
+        .insert(species.mass())
+        .insert(species.footprint())
+        .insert(species)
+        .insert(Walking(direction))
+        .insert(Steering(None))
+        .id()
 }
 
 #[derive(Component)]
@@ -69,48 +155,117 @@ pub struct Animal;
 #[derive(Component)]
 pub struct Walking(pub Vec2);
 
+/// Weight of a walker; the ice breaks faster under heavier loads
+#[derive(Component)]
+pub struct Mass(pub f32);
+
+/// Circle around the center of a walker that stands on the ice
 #[derive(Component)]
-pub struct Steering(Option<f32>);
+pub struct Footprint {
+    pub radius: f32,
+}
+
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub enum Species {
+    Regular,
+    Heavy,
+}
+
+impl Species {
+    fn random(rng: &mut impl Rng) -> Self {
+        if rng.gen::<f32>() < 0.2 {
+            Species::Heavy
+        } else {
+            Species::Regular
+        }
+    }
+
+    fn mass(&self) -> Mass {
+        match self {
+            Species::Regular => Mass(1.),
+            Species::Heavy => Mass(1.8),
+        }
+    }
+
+    fn footprint(&self) -> Footprint {
+        match self {
+            Species::Regular => Footprint { radius: 12. },
+            Species::Heavy => Footprint { radius: 16. },
+        }
+    }
+
+    fn sprite(&self) -> Sprite {
+        match self {
+            Species::Regular => Sprite::default(),
+            Species::Heavy => Sprite {
+                color: Color::rgb(0.7, 0.7, 0.8),
+                custom_size: Some(Vec2::splat(42.)),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Steering(pub Option<f32>);
 
 fn spawn_initial_animals(
     mut commands: Commands,
     textures: Res<TextureAssets>,
     mut spawn_points: ResMut<SpawnPoints>,
+    level: Res<CurrentLevel>,
+    simulation: Res<IceSimulation>,
+    mut rng: ResMut<GameRng>,
 ) {
-    commands.insert_resource(SpawnAnimalTimer(Timer::from_seconds(10., true)));
-    for _ in 0..5 {
-        let random_spawn_point = get_random_spawn_point(&mut spawn_points);
-        let random_direction = get_random_direction();
-        let mut transform = Transform::from_translation(Vec3::new(
-            random_spawn_point.x,
-            random_spawn_point.y,
-            ANIMAL_Z,
-        ));
-        transform.rotation =
-            Quat::from_rotation_z(-random_direction.angle_between(Vec2::new(0., 1.)));
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: textures.animal.clone(),
-                transform,
-                ..Default::default()
-            })
-            .insert(Level)
-            .insert(Animal)
-            .insert(Walking(random_direction))
-            .insert(Steering(None));
+    commands.insert_resource(AnimalWaves::new(level.0.waves.clone()));
+    for index in 0..level.0.initial_animals {
+        let random_spawn_point = match level.0.animal_spawns.get(index) {
+            Some(&spawn) => {
+                spawn_points.0.push(spawn.into());
+                spawn.into()
+            }
+            None => {
+                match get_random_spawn_point(&mut spawn_points, &level.0, &simulation, &mut rng.0) {
+                    Some(point) => point,
+                    None => continue,
+                }
+            }
+        };
+        let random_direction = get_random_direction(&mut rng.0);
+        let species = Species::random(&mut rng.0);
+        spawn_animal(
+            &mut commands,
+            &textures,
+            species,
+            random_spawn_point,
+            random_direction,
+        );
     }
 }
 
 fn move_animals(
     time: Res<Time>,
+    arena: Res<Arena>,
+    simulation: Res<IceSimulation>,
+    difficulty: Res<Difficulty>,
+    mut rng: ResMut<GameRng>,
     mut player_query: Query<
         (&mut Transform, &mut Walking, &mut Steering),
-        (With<Animal>, Without<Drowning>, Without<Falling>),
+        (
+            With<Animal>,
+            Without<Drowning>,
+            Without<Falling>,
+            Without<Adrift>,
+        ),
     >,
 ) {
-    let speed = 50.;
     for (mut transform, mut walking, mut steering) in player_query.iter_mut() {
-        let steering_rand: f32 = random();
+        let speed = 50.
+            * difficulty.animal_speed
+            * simulation
+                .terrain(arena.get_current_grid(&transform.translation))
+                .speed();
+        let steering_rand: f32 = rng.0.gen();
         if let Some(steering_value) = steering.0.clone() {
             let rotation = Mat2::from_angle(-steering_value * time.delta_seconds());
             walking.0 = rotation.mul_vec2(walking.0);
@@ -130,15 +285,13 @@ fn move_animals(
             walking.0.y * speed * time.delta_seconds(),
             0.,
         );
-        transform.translation += movement;
-        transform.translation = transform.translation.clamp(
-            Vec3::new(
-                -WINDOW_WIDTH / 2. + 16.,
-                -WINDOW_HEIGHT / 2. + 16.,
-                ANIMAL_Z,
-            ),
-            Vec3::new(WINDOW_WIDTH / 2. - 16., WINDOW_HEIGHT / 2. - 16., ANIMAL_Z),
-        );
+        let target = arena.clamp(transform.translation + movement, 16.);
+        // turn around in front of rocks
+        if simulation.is_rock(arena.get_current_grid(&target)) {
+            walking.0 = -walking.0;
+        } else {
+            transform.translation = target;
+        }
         transform.rotation = Quat::from_rotation_z(-walking.0.angle_between(Vec2::new(0., 1.)));
     }
 }
@@ -148,3 +301,54 @@ fn drown_animals(mut commands: Commands, mut animal_fall_events: EventReader<Ani
         commands.entity(*animal).insert(Drowning::default());
     }
 }
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum AnimalLabels {
+    MoveAnimals,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waves() -> AnimalWaves {
+        AnimalWaves::new(vec![
+            AnimalWave {
+                delay: 1.,
+                count: 2,
+            },
+            AnimalWave {
+                delay: 3.,
+                count: 1,
+            },
+        ])
+    }
+
+    #[test]
+    fn waves_come_after_their_delay() {
+        let mut waves = waves();
+
+        assert_eq!(waves.tick(Duration::from_secs_f32(0.5)), 0);
+        assert_eq!(waves.tick(Duration::from_secs_f32(0.5)), 2);
+        assert_eq!(waves.tick(Duration::from_secs(2)), 0);
+        assert_eq!(waves.tick(Duration::from_secs(1)), 1);
+    }
+
+    #[test]
+    fn the_last_wave_repeats() {
+        let mut waves = waves();
+        waves.tick(Duration::from_secs(1));
+
+        for _ in 0..3 {
+            assert_eq!(waves.tick(Duration::from_secs(3)), 1);
+        }
+        assert_eq!(waves.current(), 1);
+    }
+
+    #[test]
+    fn no_waves_spawn_nothing() {
+        let mut waves = AnimalWaves::new(vec![]);
+
+        assert_eq!(waves.tick(Duration::from_secs(100)), 0);
+    }
+}
@@ -1,16 +1,18 @@
 use crate::player::Drowning;
 use crate::GameState;
 use bevy::prelude::*;
+use std::f32::consts::PI;
 
 pub struct AnimatePlugin;
 
 impl Plugin for AnimatePlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
+        app.add_event::<LandedEvent>().add_system_set(
             SystemSet::on_update(GameState::Playing)
                 .with_system(animate_walking)
                 .with_system(animate_drowning)
-                .with_system(animate_falling),
+                .with_system(animate_falling)
+                .with_system(animate_airborne.label(AnimateLabels::Land)),
         );
     }
 }
@@ -21,6 +23,19 @@ pub struct AnimationTimer(pub Timer);
 #[derive(Component)]
 pub struct Falling;
 
+/// In the middle of a jump; the ice does not notice airborne walkers
+#[derive(Component)]
+pub struct Airborne {
+    pub timer: Timer,
+    /// Follows the walker on the ground while it is in the air
+    pub shadow: Entity,
+}
+
+pub const JUMP_SECONDS: f32 = 0.6;
+
+/// A jump ended at the position of this walker
+pub struct LandedEvent(pub Entity);
+
 fn animate_walking(
     time: Res<Time>,
     mut query: Query<
@@ -57,6 +72,32 @@ fn animate_falling(
     }
 }
 
+fn animate_airborne(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut landed_events: EventWriter<LandedEvent>,
+    mut jumpers: Query<(Entity, &mut Airborne, &mut Transform), Without<Drowning>>,
+    mut shadows: Query<&mut Transform, Without<Airborne>>,
+) {
+    for (entity, mut airborne, mut transform) in jumpers.iter_mut() {
+        airborne.timer.tick(time.delta());
+        if airborne.timer.finished() {
+            transform.scale = Vec3::splat(1.);
+            commands.entity(airborne.shadow).despawn();
+            commands.entity(entity).remove::<Airborne>();
+            landed_events.send(LandedEvent(entity));
+            continue;
+        }
+        let height = (airborne.timer.percent() * PI).sin();
+        let scale = 1. + 0.35 * height;
+        transform.scale = Vec3::new(scale, scale, 1.);
+        if let Ok(mut shadow) = shadows.get_mut(airborne.shadow) {
+            shadow.translation = transform.translation + Vec3::new(0., -12. * height, -0.1);
+            shadow.rotation = transform.rotation;
+        }
+    }
+}
+
 fn animate_drowning(time: Res<Time>, mut query: Query<(&mut Drowning, &mut Transform)>) {
     for (mut timer, mut transform) in query.iter_mut() {
         if timer.0.finished() {
@@ -66,3 +107,8 @@ fn animate_drowning(time: Res<Time>, mut query: Query<(&mut Drowning, &mut Trans
         transform.scale = Vec3::new(1.0 - timer.0.percent(), 1.0 - timer.0.percent(), 1.);
     }
 }
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum AnimateLabels {
+    Land,
+}
@@ -1,6 +1,8 @@
+use crate::actions::Actions;
+use crate::climb::{ClimbLabels, ClimbedOutEvent, Struggling};
 use crate::ice::IceLabels;
 use crate::loading::AudioAssets;
-use crate::player::{AnimalFallEvent, PlayerFallEvent};
+use crate::player::{AnimalFallEvent, Player, PlayerFallEvent};
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin};
@@ -14,7 +16,9 @@ impl Plugin for InternalAudioPlugin {
             .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_walking))
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(break_through_ice.after(IceLabels::CheckIceGrid)),
+                    .with_system(break_through_ice.after(IceLabels::CheckIceGrid))
+                    .with_system(struggle_in_water.after(ClimbLabels::StartStruggle))
+                    .with_system(climb_out.after(ClimbLabels::Struggle)),
             )
             .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(start_audio));
     }
@@ -22,12 +26,14 @@ impl Plugin for InternalAudioPlugin {
 
 struct Channels {
     walking: AudioChannel,
+    struggle: AudioChannel,
 }
 
 impl Default for Channels {
     fn default() -> Self {
         Channels {
             walking: AudioChannel::new("walking".to_owned()),
+            struggle: AudioChannel::new("struggle".to_owned()),
         }
     }
 }
@@ -56,3 +62,29 @@ fn break_through_ice(
         audio.play(audio_assets.breaking_ice.clone());
     }
 }
+
+/// Every pull on the edge of the hole makes the ice creak
+fn struggle_in_water(
+    actions: Res<Actions>,
+    player: Query<(), (With<Player>, With<Struggling>)>,
+    audio: Res<Audio>,
+    channels: Res<Channels>,
+    audio_assets: Res<AudioAssets>,
+) {
+    if actions.jump && player.iter().next().is_some() {
+        audio.set_volume_in_channel(0.2, &channels.struggle);
+        audio.play_in_channel(audio_assets.breaking_ice.clone(), &channels.struggle);
+    }
+}
+
+fn climb_out(
+    mut events: EventReader<ClimbedOutEvent>,
+    audio: Res<Audio>,
+    channels: Res<Channels>,
+    audio_assets: Res<AudioAssets>,
+) {
+    for _ in events.iter() {
+        audio.stop_channel(&channels.struggle);
+        audio.play_looped_in_channel(audio_assets.walking.clone(), &channels.walking);
+    }
+}
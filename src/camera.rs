@@ -0,0 +1,87 @@
+use crate::level::Arena;
+use crate::player::Player;
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+pub struct CameraPlugin;
+
+/// This plugin spawns the cameras and keeps the 2D camera on the player
+/// The camera never shows anything outside of the arena, unless the arena is smaller than the window.
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_exit(GameState::Loading).with_system(setup_cameras))
+            .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(center_camera))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                follow_player
+                    .label(CameraLabels::FollowPlayer)
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                anchor_to_screen
+                    .after(CameraLabels::FollowPlayer)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct MainCamera;
+
+/// Keeps a world space entity at this offset from the center of the screen
+#[derive(Component)]
+pub struct ScreenAnchor(pub Vec2);
+
+fn setup_cameras(mut commands: Commands) {
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(OrthographicCameraBundle::new_2d())
+        .insert(MainCamera);
+}
+
+fn center_camera(mut camera: Query<&mut Transform, With<MainCamera>>) {
+    for mut transform in camera.iter_mut() {
+        transform.translation.x = 0.;
+        transform.translation.y = 0.;
+    }
+}
+
+fn follow_player(
+    windows: Res<Windows>,
+    arena: Option<Res<Arena>>,
+    player: Query<&Transform, (With<Player>, Without<MainCamera>)>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let (player, arena, window) = match (player.get_single(), arena, windows.get_primary()) {
+        (Ok(player), Some(arena), Some(window)) => (player, arena, window),
+        _ => return,
+    };
+    let bound = |arena_size: f32, window_size: f32| ((arena_size - window_size) / 2.).max(0.);
+    let bound_x = bound(arena.width(), window.width());
+    let bound_y = bound(arena.height(), window.height());
+    for mut transform in camera.iter_mut() {
+        transform.translation.x = player.translation.x.clamp(-bound_x, bound_x);
+        transform.translation.y = player.translation.y.clamp(-bound_y, bound_y);
+    }
+}
+
+fn anchor_to_screen(
+    camera: Query<&Transform, With<MainCamera>>,
+    mut anchored: Query<(&ScreenAnchor, &mut Transform), Without<MainCamera>>,
+) {
+    let camera = match camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    for (ScreenAnchor(offset), mut transform) in anchored.iter_mut() {
+        transform.translation.x = camera.translation.x + offset.x;
+        transform.translation.y = camera.translation.y + offset.y;
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum CameraLabels {
+    FollowPlayer,
+}
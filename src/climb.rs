@@ -0,0 +1,347 @@
+use crate::actions::Actions;
+use crate::animal::Footprint;
+use crate::floe::Adrift;
+use crate::ice::{IceLabels, FALL_WATER_SHARE};
+use crate::level::Arena;
+use crate::loading::FontAssets;
+use crate::player::{Player, PlayerDrownEvent, PlayerFallEvent};
+use crate::simulation::{IceSimulation, Slot};
+use crate::{GameState, Level};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::cmp::Ordering;
+
+/// Progress lost per second while the player stops pulling
+const SLIP_BACK: f32 = 0.25;
+
+pub struct ClimbPlugin;
+
+/// This plugin gives a player that broke through the ice a moment to climb back out
+/// Mashing the jump key pulls the player up; every climb out makes the next one harder.
+impl Plugin for ClimbPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClimbedOutEvent>()
+            .add_system_set(SystemSet::on_enter(GameState::Countdown).with_system(reset_climb_outs))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        start_struggle
+                            .label(ClimbLabels::StartStruggle)
+                            .after(IceLabels::CheckIceGrid),
+                    )
+                    .with_system(
+                        struggle
+                            .label(ClimbLabels::Struggle)
+                            .after(ClimbLabels::StartStruggle),
+                    )
+                    .with_system(update_struggle_bar.after(ClimbLabels::Struggle))
+                    .with_system(show_climb_message.after(ClimbLabels::Struggle))
+                    .with_system(remove_climb_message),
+            );
+    }
+}
+
+/// Times the player climbed out of the water during the current run
+pub struct ClimbOuts(pub usize);
+
+/// In the water, trying to get back onto the ice
+#[derive(Component)]
+pub struct Struggling {
+    /// Time left to get out
+    pub timer: Timer,
+    /// From 0 to 1; the player is out once it is full
+    pub progress: f32,
+    /// Progress per key press
+    pub pull: f32,
+}
+
+impl Struggling {
+    /// Every earlier climb out shortens the window and asks for more key presses
+    pub fn new(climb_outs: usize) -> Self {
+        Struggling {
+            timer: Timer::from_seconds((3. - 0.4 * climb_outs as f32).max(1.5), false),
+            progress: 0.,
+            pull: 1. / (6 + 3 * climb_outs) as f32,
+        }
+    }
+}
+
+pub struct ClimbedOutEvent;
+
+#[derive(Component)]
+struct StruggleBar;
+
+#[derive(Component)]
+struct StruggleFill;
+
+#[derive(Component)]
+struct StruggleText;
+
+#[derive(Component)]
+struct ClimbMessage(Timer);
+
+fn reset_climb_outs(mut commands: Commands) {
+    commands.insert_resource(ClimbOuts(0));
+}
+
+/// The intact slot close to `translation` where the player would stand most firmly
+/// Slots the player would fall right back in from don't count.
+fn landing_slot(
+    arena: &Arena,
+    simulation: &IceSimulation,
+    translation: &Vec3,
+    radius: f32,
+) -> Option<Slot> {
+    arena
+        .slots_under(translation, radius + 1.5 * arena.grid_size as f32)
+        .into_iter()
+        .filter(|&slot| !simulation.is_water(slot) && !simulation.is_rock(slot))
+        .map(|slot| {
+            let center = arena.get_slot_center(slot);
+            let water = simulation.water_share(&arena.slots_under(&center.extend(0.), radius));
+            (slot, water, center.distance(translation.truncate()))
+        })
+        .filter(|&(_, water, _)| water < FALL_WATER_SHARE)
+        .min_by(|(_, water, distance), (_, other_water, other_distance)| {
+            water
+                .partial_cmp(other_water)
+                .unwrap_or(Ordering::Equal)
+                .then(
+                    distance
+                        .partial_cmp(other_distance)
+                        .unwrap_or(Ordering::Equal),
+                )
+        })
+        .map(|(slot, _, _)| slot)
+}
+
+fn start_struggle(
+    mut commands: Commands,
+    mut fall_events: EventReader<PlayerFallEvent>,
+    mut drown_events: EventWriter<PlayerDrownEvent>,
+    climb_outs: Res<ClimbOuts>,
+    arena: Res<Arena>,
+    simulation: Res<IceSimulation>,
+    font_assets: Res<FontAssets>,
+    player: Query<(Entity, &Transform, &Footprint), (With<Player>, Without<Struggling>)>,
+) {
+    if fall_events.iter().next().is_none() {
+        return;
+    }
+    let (entity, transform, footprint) = match player.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    // without any ice around, there is nothing to hold on to
+    if landing_slot(
+        &arena,
+        &simulation,
+        &transform.translation,
+        footprint.radius,
+    )
+    .is_none()
+    {
+        drown_events.send(PlayerDrownEvent);
+        return;
+    }
+    commands
+        .entity(entity)
+        .remove::<Adrift>()
+        .insert(Struggling::new(climb_outs.0));
+    spawn_struggle_bar(&mut commands, &font_assets);
+}
+
+pub fn spawn_struggle_bar(commands: &mut Commands, font_assets: &FontAssets) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Px(80.)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position: Rect {
+                    top: Val::Percent(25.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: UiColor(Color::NONE),
+            ..Default::default()
+        })
+        .insert(StruggleBar)
+        .insert(Level)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: String::new(),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 40.0,
+                                color: Color::rgb_u8(34, 32, 52),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(StruggleText);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(250.0), Val::Px(20.0)),
+                        padding: Rect::all(Val::Px(4.)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::rgba(0.7, 0.7, 0.7, 0.7)),
+                    ..Default::default()
+                })
+                .with_children(|bar| {
+                    bar.spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                            ..Default::default()
+                        },
+                        color: UiColor(Color::rgb(0.3, 0.55, 0.9)),
+                        ..Default::default()
+                    })
+                    .insert(StruggleFill);
+                });
+        });
+}
+
+/// Where a struggle ends: back on the ice or drowned
+#[derive(SystemParam)]
+struct StruggleOutcome<'w, 's> {
+    climb_outs: ResMut<'w, ClimbOuts>,
+    climbed_out_events: EventWriter<'w, 's, ClimbedOutEvent>,
+    drown_events: EventWriter<'w, 's, PlayerDrownEvent>,
+}
+
+fn struggle(
+    mut commands: Commands,
+    time: Res<Time>,
+    actions: Res<Actions>,
+    ice: (Res<Arena>, Res<IceSimulation>),
+    mut outcome: StruggleOutcome,
+    mut player: Query<(Entity, &mut Struggling, &mut Transform, &Footprint), With<Player>>,
+    bars: Query<Entity, With<StruggleBar>>,
+) {
+    let (arena, simulation) = ice;
+    for (entity, mut struggling, mut transform, footprint) in player.iter_mut() {
+        struggling.timer.tick(time.delta());
+        if actions.jump {
+            struggling.progress += struggling.pull;
+        } else {
+            struggling.progress = (struggling.progress - SLIP_BACK * time.delta_seconds()).max(0.);
+        }
+        let scale = 0.8 + 0.2 * struggling.progress.min(1.);
+        transform.scale = Vec3::new(scale, scale, 1.);
+        let pulled_out = struggling.progress >= 1.;
+        if !pulled_out && !struggling.timer.finished() {
+            continue;
+        }
+
+        for bar in bars.iter() {
+            commands.entity(bar).despawn_recursive();
+        }
+        commands.entity(entity).remove::<Struggling>();
+        let landing = if pulled_out {
+            landing_slot(
+                &arena,
+                &simulation,
+                &transform.translation,
+                footprint.radius,
+            )
+        } else {
+            None
+        };
+        match landing {
+            Some(slot) => {
+                let center = arena.get_slot_center(slot);
+                transform.translation.x = center.x;
+                transform.translation.y = center.y;
+                transform.scale = Vec3::splat(1.);
+                outcome.climb_outs.0 += 1;
+                outcome.climbed_out_events.send(ClimbedOutEvent);
+            }
+            None => outcome.drown_events.send(PlayerDrownEvent),
+        }
+    }
+}
+
+fn update_struggle_bar(
+    player: Query<&Struggling, With<Player>>,
+    mut fill: Query<&mut Style, With<StruggleFill>>,
+    mut text: Query<&mut Text, With<StruggleText>>,
+) {
+    let struggling = match player.get_single() {
+        Ok(struggling) => struggling,
+        Err(_) => return,
+    };
+    for mut style in fill.iter_mut() {
+        style.size.width = Val::Percent(100. * struggling.progress.min(1.));
+    }
+    let seconds_left = struggling.timer.duration().as_secs_f32() - struggling.timer.elapsed_secs();
+    for mut text in text.iter_mut() {
+        text.sections[0].value =
+            format!("Mash SPACE or A to climb out! {:.1}", seconds_left.max(0.));
+    }
+}
+
+fn show_climb_message(
+    mut commands: Commands,
+    mut events: EventReader<ClimbedOutEvent>,
+    climb_outs: Res<ClimbOuts>,
+    font_assets: Res<FontAssets>,
+) {
+    for _ in events.iter() {
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        left: Val::Px(10.),
+                        bottom: Val::Px(10.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text {
+                    sections: vec![TextSection {
+                        value: format!("Climbed out! ({} so far)", climb_outs.0),
+                        style: TextStyle {
+                            font: font_assets.fira_sans.clone(),
+                            font_size: 30.0,
+                            color: Color::rgb_u8(34, 32, 52),
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
+                ..Default::default()
+            })
+            .insert(ClimbMessage(Timer::from_seconds(2., false)))
+            .insert(Level);
+    }
+}
+
+fn remove_climb_message(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut messages: Query<(Entity, &mut ClimbMessage)>,
+) {
+    for (entity, mut message) in messages.iter_mut() {
+        message.0.tick(time.delta());
+        if message.0.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum ClimbLabels {
+    StartStruggle,
+    Struggle,
+}
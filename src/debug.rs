@@ -0,0 +1,136 @@
+use crate::animal::{Animal, Footprint};
+use crate::level::Arena;
+use crate::player::Player;
+use crate::simulation::{IceSimulation, Slot, SlotState, Terrain};
+use crate::{GameState, Level};
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+const DEBUG_Z: f32 = 9.;
+
+pub struct DebugPlugin;
+
+/// This plugin draws what the ice simulation believes about every slot
+/// Toggle the overlay with F3. It is only compiled with the `dev` feature.
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .add_system(toggle_overlay)
+            .add_system_set(SystemSet::on_enter(GameState::Countdown).with_system(spawn_overlay))
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(draw_overlay));
+    }
+}
+
+#[derive(Default)]
+struct DebugOverlay {
+    visible: bool,
+}
+
+#[derive(Component)]
+struct OverlaySprite;
+
+fn spawn_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    arena: Res<Arena>,
+    overlay: Res<DebugOverlay>,
+) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: arena.grid_x as u32,
+            height: arena.grid_y as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0u8, 0u8, 0u8, 0u8],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: images.add(image),
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(arena.width(), arena.height())),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0., 0., DEBUG_Z),
+            visibility: Visibility {
+                is_visible: overlay.visible,
+            },
+            ..Default::default()
+        })
+        .insert(OverlaySprite)
+        .insert(Level);
+}
+
+fn toggle_overlay(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut sprites: Query<&mut Visibility, With<OverlaySprite>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F3) {
+        return;
+    }
+    overlay.visible = !overlay.visible;
+    for mut visibility in sprites.iter_mut() {
+        visibility.is_visible = overlay.visible;
+    }
+}
+
+fn draw_overlay(
+    overlay: Res<DebugOverlay>,
+    mut images: ResMut<Assets<Image>>,
+    simulation: Res<IceSimulation>,
+    arena: Res<Arena>,
+    sprites: Query<&Handle<Image>, With<OverlaySprite>>,
+    walkers: Query<(&Transform, &Footprint), Or<(With<Player>, With<Animal>)>>,
+) {
+    if !overlay.visible {
+        return;
+    }
+    let image = match sprites.get_single() {
+        Ok(handle) => images
+            .get_mut(handle.clone())
+            .expect("Failed to find the debug overlay texture"),
+        Err(_) => return,
+    };
+    let width = simulation.width();
+    let height = simulation.height();
+    for y in 0..height {
+        for x in 0..width {
+            let color = slot_color(&simulation, (x, y));
+            let pixel = ((height - 1 - y) * width + x) * 4;
+            image.data[pixel..pixel + 4].copy_from_slice(&color);
+        }
+    }
+    for (transform, footprint) in walkers.iter() {
+        for (x, y) in arena.slots_under(&transform.translation, footprint.radius) {
+            if simulation.contains((x, y)) {
+                let pixel = ((height - 1 - y) * width + x) * 4;
+                image.data[pixel..pixel + 4].copy_from_slice(&[255, 0, 255, 220]);
+            }
+        }
+    }
+}
+
+/// Ice is green, cracks go from yellow to red while they age, water is blue, rocks grey and the
+/// shore sand coloured
+/// Snow and slush only show on intact slots, as white and grey-blue
+fn slot_color(simulation: &IceSimulation, slot: Slot) -> [u8; 4] {
+    match simulation.state(slot) {
+        SlotState::Ice { .. } => match simulation.terrain(slot) {
+            Terrain::Ice => [0, 200, 0, 60],
+            Terrain::Snow => [255, 255, 255, 120],
+            Terrain::Slush => [120, 150, 200, 140],
+        },
+        SlotState::Cracks { since, .. } => {
+            let age = ((simulation.elapsed() - since)
+                / (simulation.config.break_delay * simulation.thickness(slot) as f64))
+                .clamp(0., 1.);
+            [255, (220. * (1. - age)) as u8, 0, 160]
+        }
+        SlotState::Brocken { .. } => [0, 80, 255, 160],
+        SlotState::OpenWater => [0, 40, 160, 160],
+        SlotState::Rock => [128, 128, 128, 160],
+        SlotState::Shore => [200, 170, 90, 160],
+    }
+}
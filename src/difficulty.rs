@@ -0,0 +1,134 @@
+use crate::animal::AnimalLabels;
+use crate::ice::IceLabels;
+use crate::level::{CurrentLevel, DifficultyKeyframe};
+use crate::ui::GameStopWatch;
+use crate::GameState;
+use bevy::prelude::*;
+
+pub struct DifficultyPlugin;
+
+/// This plugin ramps up the difficulty along the keyframes of the current level
+/// The curve is keyed off the time survived, so a continued run picks up where it was saved.
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Difficulty::default())
+            .add_system_set(SystemSet::on_enter(GameState::Countdown).with_system(reset_difficulty))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(
+                    update_difficulty
+                        .label(DifficultyLabels::UpdateDifficulty)
+                        .before(AnimalLabels::MoveAnimals)
+                        .before(IceLabels::CheckIceGrid),
+                ),
+            );
+    }
+}
+
+pub struct Difficulty {
+    pub spawn_rate: f32,
+    pub animal_speed: f32,
+    pub fragility: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty {
+            spawn_rate: 1.,
+            animal_speed: 1.,
+            fragility: 1.,
+        }
+    }
+}
+
+impl Difficulty {
+    /// Interpolate between the keyframes around `seconds`, holding the first and last one
+    pub fn at(keyframes: &[DifficultyKeyframe], seconds: f32) -> Self {
+        let next = keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > seconds);
+        let (from, to) = match next {
+            None => match keyframes.last() {
+                Some(last) => (last, last),
+                None => return Difficulty::default(),
+            },
+            Some(0) => (&keyframes[0], &keyframes[0]),
+            Some(index) => (&keyframes[index - 1], &keyframes[index]),
+        };
+        let progress = if to.time > from.time {
+            ((seconds - from.time) / (to.time - from.time)).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let lerp = |from: f32, to: f32| from + (to - from) * progress;
+
+        Difficulty {
+            spawn_rate: lerp(from.spawn_rate, to.spawn_rate),
+            animal_speed: lerp(from.animal_speed, to.animal_speed),
+            fragility: lerp(from.fragility, to.fragility),
+        }
+    }
+}
+
+fn reset_difficulty(mut difficulty: ResMut<Difficulty>, level: Res<CurrentLevel>) {
+    *difficulty = Difficulty::at(&level.0.difficulty, 0.);
+}
+
+fn update_difficulty(
+    mut difficulty: ResMut<Difficulty>,
+    level: Res<CurrentLevel>,
+    stop_watch: Res<GameStopWatch>,
+) {
+    *difficulty = Difficulty::at(&level.0.difficulty, stop_watch.0.elapsed_secs());
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum DifficultyLabels {
+    UpdateDifficulty,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(
+        time: f32,
+        spawn_rate: f32,
+        animal_speed: f32,
+        fragility: f32,
+    ) -> DifficultyKeyframe {
+        DifficultyKeyframe {
+            time,
+            spawn_rate,
+            animal_speed,
+            fragility,
+        }
+    }
+
+    fn factors(difficulty: Difficulty) -> (f32, f32, f32) {
+        (
+            difficulty.spawn_rate,
+            difficulty.animal_speed,
+            difficulty.fragility,
+        )
+    }
+
+    #[test]
+    fn keyframes_are_interpolated() {
+        let keyframes = [keyframe(10., 1., 1., 1.), keyframe(20., 2., 3., 5.)];
+
+        assert_eq!(factors(Difficulty::at(&keyframes, 15.)), (1.5, 2., 3.));
+    }
+
+    #[test]
+    fn the_first_and_last_keyframe_hold() {
+        let keyframes = [keyframe(10., 1., 1., 1.), keyframe(20., 2., 3., 5.)];
+
+        assert_eq!(factors(Difficulty::at(&keyframes, 0.)), (1., 1., 1.));
+        assert_eq!(factors(Difficulty::at(&keyframes, 30.)), (2., 3., 5.));
+    }
+
+    #[test]
+    fn no_keyframes_keep_the_default() {
+        assert_eq!(factors(Difficulty::at(&[], 30.)), (1., 1., 1.));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::animal::Animal;
+use crate::animate::Falling;
+use crate::ice::{IceLabels, DATA_PER_PIXEL, ICE_HOLE_Z};
+use crate::level::Arena;
+use crate::loading::{CracksLayer, TextureAssets};
+use crate::player::{AnimalFallEvent, Drowning, Player, PlayerFallEvent};
+use crate::simulation::Slot;
+use crate::{GameState, Level};
+use bevy::math::Mat2;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+pub const FLOE_Z: f32 = ICE_HOLE_Z + 0.5;
+const FLOE_SPEED: f32 = 8.;
+const FLOE_SPIN: f32 = 0.15;
+
+pub struct FloePlugin;
+
+/// This plugin turns ice cut off by water into floes
+/// Floes drift away, tilt and sink within a few seconds, taking everyone standing on them along.
+impl Plugin for FloePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FloeDetachedEvent>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(
+                    spawn_floes
+                        .label(FloeLabels::SpawnFloes)
+                        .after(IceLabels::CheckIceGrid),
+                )
+                .with_system(drift_floes.after(FloeLabels::SpawnFloes)),
+        );
+    }
+}
+
+pub struct FloeDetachedEvent(pub Vec<Slot>);
+
+#[derive(Component)]
+pub struct Floe {
+    /// The slots the floe was cut from
+    pub slots: Vec<Slot>,
+    pub velocity: Vec2,
+    pub spin: f32,
+    pub angle: f32,
+    pub sinking: Timer,
+}
+
+/// Marks a walker carried by a floe; its position relative to the floe center is kept
+#[derive(Component)]
+pub struct Adrift {
+    pub floe: Entity,
+    pub offset: Vec2,
+}
+
+fn spawn_floes(
+    mut commands: Commands,
+    mut events: EventReader<FloeDetachedEvent>,
+    mut images: ResMut<Assets<Image>>,
+    textures: Res<TextureAssets>,
+    cracks_layer: Res<CracksLayer>,
+    arena: Res<Arena>,
+    walkers: Query<
+        (Entity, &Transform),
+        (
+            Or<(With<Player>, With<Animal>)>,
+            Without<Drowning>,
+            Without<Falling>,
+            Without<Adrift>,
+        ),
+    >,
+) {
+    for FloeDetachedEvent(slots) in events.iter() {
+        let floe = spawn_floe(
+            &mut commands,
+            &mut images,
+            &textures,
+            &cracks_layer,
+            &arena,
+            slots,
+        );
+        let bounds_center = floe_center(slots, &arena);
+
+        for (entity, transform) in walkers.iter() {
+            if slots.contains(&arena.get_current_grid(&transform.translation)) {
+                commands.entity(entity).insert(Adrift {
+                    floe,
+                    offset: transform.translation.truncate() - bounds_center,
+                });
+            }
+        }
+    }
+}
+
+/// Spawn a floe made of `slots`, drifting away from the middle of the arena
+pub fn spawn_floe(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    textures: &TextureAssets,
+    cracks_layer: &CracksLayer,
+    arena: &Arena,
+    slots: &[Slot],
+) -> Entity {
+    let image = floe_image(slots, arena, images, textures, cracks_layer);
+    let center = slots
+        .iter()
+        .fold(Vec2::ZERO, |sum, slot| sum + arena.get_slot_center(*slot))
+        / slots.len() as f32;
+    let bounds_center = floe_center(slots, arena);
+
+    let direction = if center == Vec2::ZERO {
+        Vec2::X
+    } else {
+        center.normalize()
+    };
+    let velocity = direction * FLOE_SPEED;
+    let spin = if slots.len().is_multiple_of(2) {
+        FLOE_SPIN
+    } else {
+        -FLOE_SPIN
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: images.add(image),
+            transform: Transform::from_xyz(bounds_center.x, bounds_center.y, FLOE_Z),
+            ..Default::default()
+        })
+        .insert(Floe {
+            slots: slots.to_vec(),
+            velocity,
+            spin,
+            angle: 0.,
+            sinking: Timer::from_seconds(4., false),
+        })
+        .insert(Level)
+        .id()
+}
+
+/// Center of the bounding box of `slots`, where the floe sprite starts out
+fn floe_center(slots: &[Slot], arena: &Arena) -> Vec2 {
+    let (min, max) = slot_bounds(slots);
+    (arena.get_slot_center(min) + arena.get_slot_center(max)) / 2.
+}
+
+fn drift_floes(
+    mut commands: Commands,
+    time: Res<Time>,
+    arena: Res<Arena>,
+    mut floes: Query<(Entity, &mut Floe, &mut Transform, &mut Sprite)>,
+    mut passengers: Query<(Entity, &Adrift, &mut Transform, Option<&Player>), Without<Floe>>,
+    mut player_fall_events: EventWriter<PlayerFallEvent>,
+    mut animal_fall_events: EventWriter<AnimalFallEvent>,
+) {
+    for (entity, mut floe, mut transform, mut sprite) in floes.iter_mut() {
+        floe.sinking.tick(time.delta());
+        floe.angle += floe.spin * time.delta_seconds();
+        transform.translation += (floe.velocity * time.delta_seconds()).extend(0.);
+        transform.rotation = Quat::from_rotation_z(floe.angle);
+        let sunk = floe.sinking.percent();
+        // tilt by squashing the floe while it slowly goes under
+        transform.scale = Vec3::new(1. - 0.2 * sunk, 1. - 0.4 * sunk, 1.);
+        sprite.color.set_a(1. - sunk);
+
+        if floe.sinking.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for (entity, adrift, mut transform, player) in passengers.iter_mut() {
+        let (floe, floe_transform) = match floes.get(adrift.floe) {
+            Ok((_, floe, floe_transform, _)) => (floe, floe_transform),
+            Err(_) => {
+                commands.entity(entity).remove::<Adrift>();
+                continue;
+            }
+        };
+        let rotation = Mat2::from_angle(floe.angle);
+        let offset = rotation.mul_vec2(adrift.offset * floe_transform.scale.truncate());
+        transform.translation.x = floe_transform.translation.x + offset.x;
+        transform.translation.y = floe_transform.translation.y + offset.y;
+        // floes may drift out of the arena, their passengers stay at its edge
+        transform.translation = arena.clamp(transform.translation, 16.);
+
+        if floe.sinking.finished() {
+            commands.entity(entity).remove::<Adrift>();
+            if player.is_some() {
+                player_fall_events.send(PlayerFallEvent);
+            } else {
+                animal_fall_events.send(AnimalFallEvent(entity));
+            }
+        }
+    }
+}
+
+fn slot_bounds(slots: &[Slot]) -> (Slot, Slot) {
+    slots.iter().fold(
+        ((usize::MAX, usize::MAX), (0, 0)),
+        |((min_x, min_y), (max_x, max_y)), &(x, y)| {
+            ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+        },
+    )
+}
+
+/// Cut the floe out of the ice texture and the cracks painted on it
+/// The ice texture is stretched over the whole arena, so it is sampled at the scaled position.
+fn floe_image(
+    slots: &[Slot],
+    arena: &Arena,
+    images: &Assets<Image>,
+    textures: &TextureAssets,
+    cracks_layer: &CracksLayer,
+) -> Image {
+    let ice = images
+        .get(textures.ice.clone())
+        .expect("Failed to find the ice texture");
+    let ice_size = ice.texture_descriptor.size;
+    let (ice_x, ice_y) = (ice_size.width as usize, ice_size.height as usize);
+    let grid_size = arena.grid_size;
+    let ((min_x, min_y), (max_x, max_y)) = slot_bounds(slots);
+    let width = (max_x - min_x + 1) * grid_size;
+    let height = (max_y - min_y + 1) * grid_size;
+    let mut data = vec![0u8; width * height * DATA_PER_PIXEL];
+
+    for &(x, y) in slots {
+        let (top, left) = arena.slot_pixel_origin((x, y));
+        let floe_top = (max_y - y) * grid_size;
+        let floe_left = (x - min_x) * grid_size;
+        for row in 0..grid_size {
+            for column in 0..grid_size {
+                let crack = cracks_layer.pixel(images, top + row, left + column);
+                let ice_row = (top + row) * ice_y / arena.pixel_height();
+                let ice_column = (left + column) * ice_x / arena.pixel_width();
+                let ice_pixel = (ice_row * ice_x + ice_column) * DATA_PER_PIXEL;
+                let floe_pixel = ((floe_top + row) * width + floe_left + column) * DATA_PER_PIXEL;
+                let crack_alpha = crack[3] as f32 / 255.;
+                for channel in 0..3 {
+                    data[floe_pixel + channel] =
+                        (ice.data[ice_pixel + channel] as f32 * (1. - crack_alpha)
+                            + crack[channel] as f32 * crack_alpha) as u8;
+                }
+                data[floe_pixel + 3] = u8::MAX;
+            }
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: width as u32,
+            height: height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum FloeLabels {
+    SpawnFloes,
+}
@@ -0,0 +1,98 @@
+use crate::menu::ButtonColors;
+use crate::GameState;
+use bevy::input::gamepad::Gamepads;
+use bevy::prelude::*;
+
+/// Seconds a button has to be on screen before the gamepad can press it
+/// Keeps the jump button, still mashed while drowning, from pressing "Try again!".
+const PRESS_DELAY: f32 = 0.5;
+
+pub struct GamepadPlugin;
+
+/// This plugin lets a gamepad press the buttons of the menu and the "Try again!" button
+/// The D-pad moves the focus between buttons and the south button clicks the focused one.
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ButtonFocus>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(press_buttons.label(GamepadLabels::PressButtons)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(press_buttons.label(GamepadLabels::PressButtons)),
+            );
+    }
+}
+
+struct ButtonFocus {
+    /// Index of the focused button, counted in spawn order
+    index: usize,
+    /// Runs while any button is on screen
+    shown: Timer,
+}
+
+impl Default for ButtonFocus {
+    fn default() -> Self {
+        ButtonFocus {
+            index: 0,
+            shown: Timer::from_seconds(PRESS_DELAY, false),
+        }
+    }
+}
+
+/// Clicks the focused button the way the mouse would
+/// Systems handling clicks have to run after [GamepadLabels::PressButtons] to see the click before
+/// the UI resets it.
+fn press_buttons(
+    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    button_colors: Res<ButtonColors>,
+    mut focus: ResMut<ButtonFocus>,
+    mut buttons: Query<(Entity, &mut Interaction, &mut UiColor), With<Button>>,
+) {
+    if gamepads.iter().next().is_none() {
+        return;
+    }
+    let mut buttons: Vec<_> = buttons.iter_mut().collect();
+    if buttons.is_empty() {
+        *focus = ButtonFocus::default();
+        return;
+    }
+    focus.shown.tick(time.delta());
+    buttons.sort_by_key(|(entity, _, _)| entity.id());
+    let just_pressed = |button_type| {
+        gamepads
+            .iter()
+            .any(|&gamepad| gamepad_buttons.just_pressed(GamepadButton(gamepad, button_type)))
+    };
+
+    let count = buttons.len();
+    focus.index %= count;
+    if just_pressed(GamepadButtonType::DPadDown) {
+        focus.index = (focus.index + 1) % count;
+    }
+    if just_pressed(GamepadButtonType::DPadUp) {
+        focus.index = (focus.index + count - 1) % count;
+    }
+    for (index, (_, interaction, color)) in buttons.iter_mut().enumerate() {
+        // leave buttons under the mouse to the mouse
+        if **interaction == Interaction::None {
+            **color = if index == focus.index {
+                button_colors.hovered
+            } else {
+                button_colors.normal
+            };
+        }
+    }
+    if focus.shown.finished() && just_pressed(GamepadButtonType::South) {
+        let (_, interaction, _) = &mut buttons[focus.index];
+        **interaction = Interaction::Clicked;
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum GamepadLabels {
+    PressButtons,
+}
@@ -1,52 +1,122 @@
-use crate::animal::Animal;
-use crate::animate::Falling;
-use crate::loading::{CracksData, CracksLayer, PixelData, TextureAssets};
-use crate::player::{AnimalFallEvent, Drowning, Player, PlayerFallEvent};
-use crate::{GameState, Level, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::animal::{Animal, AnimalLabels, Footprint, Mass, Walking};
+use crate::animate::{Airborne, AnimateLabels, Falling, LandedEvent};
+use crate::climb::Struggling;
+use crate::difficulty::{Difficulty, DifficultyLabels};
+use crate::floe::{Adrift, FloeDetachedEvent};
+use crate::level::{Arena, CurrentLevel, LevelData, ThicknessMap};
+use crate::loading::{CracksData, CracksLayer, PixelRect, TextureAssets};
+use crate::player::{AnimalFallEvent, Drowning, Gait, Player, PlayerFallEvent, PlayerLabels};
+use crate::rng::{GameRng, RngLabels};
+use crate::shore::Safe;
+use crate::simulation::{IceConfig, IceEvent, IceSimulation, Occupant, Slot, SlotState, Terrain};
+use crate::temperature::{Temperature, TemperatureLabels};
+use crate::{GameState, Level};
+use bevy::ecs::system::SystemParam;
 use bevy::math::Mat2;
 use bevy::prelude::*;
-use rand::random;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use rand::Rng;
 use std::f32::consts::PI;
+use std::time::Duration;
 
-const GRID_SIZE: usize = 10;
-const GRID_X: usize = 80;
-const GRID_Y: usize = 60;
-const ICE_X: usize = 800;
-const ICE_Y: usize = 600;
-const CRACKS_X: usize = 32;
-const CRACKS_Y: usize = 32;
-const DATA_PER_PIXEL: usize = 4;
+pub const DATA_PER_PIXEL: usize = 4;
+const MAX_THICKNESS: f32 = 3.;
+pub const ICE_THICKNESS_Z: f32 = 0.5;
+/// Above the cracks, so snow can hide them
+pub const ICE_TERRAIN_Z: f32 = 2.;
 pub const ICE_HOLE_Z: f32 = 3.;
-pub const SPAWN_BORDER: f32 = 200.;
+/// Pixels per second a walker slips toward water right next to it
+const SLIDE_SPEED: f32 = 30.;
+/// Walkers fall in once more than this share of their footprint is water
+pub const FALL_WATER_SHARE: f32 = 0.5;
+/// Random points tried before giving up on finding a spawn point
+const SPAWN_ATTEMPTS: usize = 1000;
+/// Preferred distance between two spawn points
+const SPAWN_DISTANCE: f32 = 100.;
 
 pub struct IcePlugin;
 
 impl Plugin for IcePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CrackTheIceTimer>()
-            .add_event::<BreakIceEvent>()
+            .init_resource::<FadeCracksTimer>()
+            .init_resource::<RefreezeTimer>()
+            .add_event::<WaterChangedEvent>()
+            .add_event::<SpreadCracksEvent>()
             .add_system_set(
-                SystemSet::on_enter(GameState::Countdown)
-                    .with_system(prepare_cracks_layer.exclusive_system().at_start()),
+                SystemSet::on_enter(GameState::Countdown).with_system(
+                    prepare_ice
+                        .exclusive_system()
+                        .at_start()
+                        .after(RngLabels::Reseed),
+                ),
             )
             .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(spawn_ice))
+            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(fit_ice_to_arena))
             .add_system_set(
-                SystemSet::on_enter(GameState::Countdown).with_system(spawn_cracks_layer),
+                SystemSet::on_enter(GameState::Countdown)
+                    .with_system(spawn_cracks_layer)
+                    .with_system(
+                        spawn_ice_simulation
+                            .label(IceLabels::SpawnIceSimulation)
+                            .before(PlayerLabels::SpawnPlayer),
+                    ),
             )
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(crack_the_ice)
+                    .with_system(
+                        apply_fragility
+                            .after(TemperatureLabels::UpdateTemperature)
+                            .after(DifficultyLabels::UpdateDifficulty)
+                            .before(IceLabels::CheckIceGrid),
+                    )
+                    .with_system(
+                        crack_the_ice
+                            .after(AnimalLabels::MoveAnimals)
+                            .before(IceLabels::CheckIceGrid),
+                    )
+                    .with_system(
+                        slide_into_water
+                            .label(IceLabels::SlideIntoWater)
+                            .after(AnimalLabels::MoveAnimals)
+                            .after(PlayerLabels::MovePlayer)
+                            .before(IceLabels::CheckIceGrid),
+                    )
                     .with_system(check_ice_grid.label(IceLabels::CheckIceGrid))
                     .with_system(
-                        break_ice
+                        paint_water
                             .label(IceLabels::BreakIce)
                             .after(IceLabels::CheckIceGrid),
+                    )
+                    .with_system(fade_cracks.after(IceLabels::CheckIceGrid))
+                    .with_system(
+                        stamp_landings
+                            .after(AnimateLabels::Land)
+                            .before(IceLabels::CheckIceGrid),
+                    )
+                    .with_system(
+                        paint_spread_cracks
+                            .after(IceLabels::CheckIceGrid)
+                            .before(IceLabels::BreakIce),
                     ),
             );
     }
 }
 
-fn prepare_cracks_layer(world: &mut World) {
+/// Build the simulation for the current level before the cracks layer paints its initial cracks
+fn prepare_ice(world: &mut World) {
+    let level = world
+        .get_resource::<CurrentLevel>()
+        .expect("Failed to get the current level")
+        .0
+        .clone();
+    let simulation = {
+        let mut rng = world
+            .get_resource_mut::<GameRng>()
+            .expect("Failed to get the game rng");
+        new_ice_simulation(&level, &mut rng.0)
+    };
+    world.insert_resource(simulation);
     let cracks_layer = CracksLayer::from_world(world);
     world.insert_resource(cracks_layer);
     world.insert_resource(SpawnPoints(vec![]));
@@ -54,54 +124,225 @@ fn prepare_cracks_layer(world: &mut World) {
 
 pub struct SpawnPoints(pub Vec<Vec2>);
 
-fn spawn_ice(mut commands: Commands, textures: Res<TextureAssets>) {
-    commands.spawn_bundle(SpriteBundle {
-        texture: textures.ice.clone(),
-        ..Default::default()
-    });
+/// The ice texture below everything else, covering the whole arena
+#[derive(Component)]
+struct IceBackground;
+
+fn spawn_ice(mut commands: Commands, textures: Res<TextureAssets>, arena: Res<Arena>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: textures.ice.clone(),
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(arena.width(), arena.height())),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(IceBackground);
+}
+
+/// Picking another level in the menu changes the size of the arena
+fn fit_ice_to_arena(arena: Res<Arena>, mut background: Query<&mut Sprite, With<IceBackground>>) {
+    if !arena.is_changed() {
+        return;
+    }
+    for mut sprite in background.iter_mut() {
+        sprite.custom_size = Some(Vec2::new(arena.width(), arena.height()));
+    }
 }
 
-fn spawn_cracks_layer(mut commands: Commands, textures: Res<CracksLayer>) {
+fn spawn_cracks_layer(mut commands: Commands, cracks_layer: Res<CracksLayer>, arena: Res<Arena>) {
+    for (tile, rect) in cracks_layer.tiles() {
+        let center = Vec2::new(
+            (rect.left as f32 + rect.columns as f32 / 2.) - arena.width() / 2.,
+            arena.height() / 2. - (rect.top as f32 + rect.rows as f32 / 2.),
+        );
+        commands
+            .spawn_bundle(SpriteBundle {
+                texture: tile.clone(),
+                transform: Transform::from_xyz(center.x, center.y, 1.),
+                ..Default::default()
+            })
+            .insert(Level);
+    }
+}
+
+fn spawn_ice_simulation(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    simulation: Res<IceSimulation>,
+    arena: Res<Arena>,
+) {
+    let mut water = Image::new_fill(
+        Extent3d {
+            width: arena.pixel_width() as u32,
+            height: arena.pixel_height() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0u8, 0u8, 0u8, 0u8],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    for y in 0..simulation.height() {
+        for x in 0..simulation.width() {
+            if simulation.is_water((x, y)) {
+                paint_water_slot(&mut water, &simulation, &arena, (x, y));
+            }
+        }
+    }
+    let water_layer = images.add(water);
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: water_layer.clone(),
+            transform: Transform::from_xyz(0., 0., ICE_HOLE_Z),
+            ..Default::default()
+        })
+        .insert(Level);
+    commands.insert_resource(WaterLayer { layer: water_layer });
     commands
         .spawn_bundle(SpriteBundle {
-            texture: textures.layer.clone(),
-            transform: Transform::from_xyz(0., 0., 1.),
+            texture: images.add(thickness_image(&simulation)),
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(arena.width(), arena.height())),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0., 0., ICE_THICKNESS_Z),
             ..Default::default()
         })
         .insert(Level);
-    commands.insert_resource(IceGrid::default());
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: images.add(terrain_image(&simulation)),
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(arena.width(), arena.height())),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0., 0., ICE_TERRAIN_Z),
+            ..Default::default()
+        })
+        .insert(Level);
+}
+
+/// One pixel per slot; thin ice is tinted dark and thick ice is brightened
+fn thickness_image(simulation: &IceSimulation) -> Image {
+    let mut data = Vec::with_capacity(simulation.width() * simulation.height() * DATA_PER_PIXEL);
+    for row in (0..simulation.height()).rev() {
+        for column in 0..simulation.width() {
+            let thickness = simulation.thickness((column, row));
+            if thickness < 1. {
+                let alpha = ((1. - thickness) * 0.6).clamp(0., 1.);
+                data.extend_from_slice(&[20, 40, 90, (alpha * 255.) as u8]);
+            } else {
+                let alpha = ((thickness - 1.) / (MAX_THICKNESS - 1.) * 0.4).clamp(0., 1.);
+                data.extend_from_slice(&[255, 255, 255, (alpha * 255.) as u8]);
+            }
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: simulation.width() as u32,
+            height: simulation.height() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// One pixel per slot for rocks, shore, snow and slush; everything else is transparent
+fn terrain_image(simulation: &IceSimulation) -> Image {
+    let mut data = Vec::with_capacity(simulation.width() * simulation.height() * DATA_PER_PIXEL);
+    for row in (0..simulation.height()).rev() {
+        for column in 0..simulation.width() {
+            let color = if simulation.is_rock((column, row)) {
+                ROCK_COLOR
+            } else if simulation.is_shore((column, row)) {
+                SHORE_COLOR
+            } else {
+                match simulation.terrain((column, row)) {
+                    Terrain::Ice => [0, 0, 0, 0],
+                    Terrain::Snow => SNOW_COLOR,
+                    Terrain::Slush => SLUSH_COLOR,
+                }
+            };
+            data.extend_from_slice(&color);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: simulation.width() as u32,
+            height: simulation.height() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
 }
 
-pub struct CrackTheIceTimer(Timer);
+const ROCK_COLOR: [u8; 4] = [96, 92, 88, 255];
+const SHORE_COLOR: [u8; 4] = [196, 184, 150, 255];
+const SNOW_COLOR: [u8; 4] = [244, 247, 252, 235];
+const SLUSH_COLOR: [u8; 4] = [140, 160, 178, 150];
+
+/// Seconds between two rounds of painting cracks under walkers at the reference temperature
+pub const CRACK_INTERVAL: f32 = 0.3;
+
+pub struct CrackTheIceTimer(pub Timer);
 
 impl Default for CrackTheIceTimer {
     fn default() -> Self {
-        CrackTheIceTimer(Timer::from_seconds(0.3, true))
+        CrackTheIceTimer(Timer::from_seconds(CRACK_INTERVAL, true))
     }
 }
 
+/// Warm weather and a later stage of the run make the ice crack, break and spread faster
+fn apply_fragility(
+    temperature: Res<Temperature>,
+    difficulty: Res<Difficulty>,
+    mut simulation: ResMut<IceSimulation>,
+    mut crack_timer: ResMut<CrackTheIceTimer>,
+) {
+    let fragility = temperature.fragility() * difficulty.fragility;
+    let reference = IceConfig::default();
+    simulation.config.break_delay = reference.break_delay / fragility as f64;
+    simulation.config.spread_chance = reference.spread_chance * fragility as f64;
+    crack_timer
+        .0
+        .set_duration(Duration::from_secs_f32(CRACK_INTERVAL / fragility));
+}
+
 fn crack_the_ice(
     player: Query<
-        &Transform,
+        (&Transform, &Walking, &Mass, &Gait),
         (
             With<Player>,
             Without<Animal>,
             Without<Falling>,
+            Without<Airborne>,
             Without<Drowning>,
+            Without<Struggling>,
+            Without<Adrift>,
         ),
     >,
     animals: Query<
-        &Transform,
+        (&Transform, &Walking, &Mass),
         (
             With<Animal>,
             Without<Player>,
             Without<Falling>,
             Without<Drowning>,
+            Without<Adrift>,
         ),
     >,
     mut images: ResMut<Assets<Image>>,
-    textures: Res<CracksLayer>,
+    cracks_layer: Res<CracksLayer>,
     cracks: Res<CracksData>,
+    arena: Res<Arena>,
+    mut rng: ResMut<GameRng>,
     mut timer: ResMut<CrackTheIceTimer>,
     time: Res<Time>,
 ) {
@@ -109,273 +350,686 @@ fn crack_the_ice(
     if !timer.0.just_finished() {
         return;
     }
-    let cracks_layer = images
-        .get_mut(textures.layer.clone())
-        .expect("Failed to find the cracks_layer texture");
-    if let Ok(player_transform) = player.get_single() {
-        crack_ice_at(&player_transform.translation, &cracks, cracks_layer);
+    if let Ok((player_transform, walking, mass, gait)) = player.get_single() {
+        crack_ice_at(
+            &player_transform.translation,
+            walking.0,
+            mass.0 * gait.load(),
+            &arena,
+            &cracks,
+            &mut rng.0,
+            &cracks_layer,
+            &mut images,
+        );
     }
 
-    for animal_transform in animals.iter() {
-        crack_ice_at(&animal_transform.translation, &cracks, cracks_layer);
+    for (animal_transform, walking, mass) in animals.iter() {
+        crack_ice_at(
+            &animal_transform.translation,
+            walking.0,
+            mass.0,
+            &arena,
+            &cracks,
+            &mut rng.0,
+            &cracks_layer,
+            &mut images,
+        );
     }
 }
 
-fn crack_ice_at(translation: &Vec3, cracks: &CracksData, cracks_layer: &mut Image) {
-    let center = Vec2::new(
-        translation.x + ICE_X as f32 / 2.,
-        ICE_Y as f32 / 2. - translation.y,
-    );
-    let center: (usize, usize) = (
-        center.x.clamp(0., ICE_X as f32 - 1.) as usize,
-        center.y.clamp(0., ICE_Y as f32 - 1.) as usize,
-    );
+/// Stamp a random crack onto the cracks layer, turned to point along `direction`
+/// Coming down from a jump hits the ice twice as hard as a step
+fn stamp_landings(
+    mut events: EventReader<LandedEvent>,
+    walkers: Query<(&Transform, &Walking, &Mass)>,
+    mut images: ResMut<Assets<Image>>,
+    cracks_layer: Res<CracksLayer>,
+    cracks: Res<CracksData>,
+    arena: Res<Arena>,
+    mut rng: ResMut<GameRng>,
+) {
+    for LandedEvent(entity) in events.iter() {
+        if let Ok((transform, walking, mass)) = walkers.get(*entity) {
+            crack_ice_at(
+                &transform.translation,
+                walking.0,
+                2. * mass.0,
+                &arena,
+                &cracks,
+                &mut rng.0,
+                &cracks_layer,
+                &mut images,
+            );
+        }
+    }
+}
 
-    let cracks = cracks.random();
+fn crack_ice_at(
+    translation: &Vec3,
+    direction: Vec2,
+    intensity: f32,
+    arena: &Arena,
+    cracks: &CracksData,
+    rng: &mut impl Rng,
+    cracks_layer: &CracksLayer,
+    images: &mut Assets<Image>,
+) {
+    let center = (
+        (translation.x + arena.width() / 2.) as i64,
+        (arena.height() / 2. - translation.y) as i64,
+    );
+    let stamp = cracks.random(rng);
+    // look up every covered pixel in the unrotated stamp, so the rotation leaves no gaps
+    let rotation = Mat2::from_angle(-Vec2::Y.angle_between(direction));
+    let radius = stamp.radius().ceil() as i64;
+    let top = (center.1 - radius).max(0);
+    let left = (center.0 - radius).max(0);
+    let rect = PixelRect {
+        top: top as usize,
+        left: left as usize,
+        rows: (center.1 + radius - top).max(0) as usize,
+        columns: (center.0 + radius - left).max(0) as usize,
+    };
 
-    for PixelData {
-        row,
-        column,
-        offset,
-        data,
-    } in cracks
-    {
-        let ice_index = (
-            center.0 as i64 + *row as i64 - (CRACKS_X as f32 / 2.) as i64,
-            center.1 as i64 + *column as i64 - (CRACKS_Y as f32 / 2.) as i64,
+    cracks_layer.paint(images, rect, |row, column, data| {
+        let offset = Vec2::new(
+            (column as i64 - center.0) as f32 + 0.5,
+            (center.1 - row as i64) as f32 - 0.5,
         );
-        if ice_index.0 < 0
-            || ice_index.0 >= ICE_X as i64
-            || ice_index.1 < 0
-            || ice_index.1 >= ICE_Y as i64
-        {
-            continue;
-        };
-        let ice_pixel = (ice_index.1 as usize * ICE_X + ice_index.0 as usize) * DATA_PER_PIXEL;
-        cracks_layer.data[ice_pixel + offset] =
-            cracks_layer.data[ice_pixel + offset].saturating_add(*data);
+        if let Some(pixel) = stamp.sample(rotation.mul_vec2(offset)) {
+            for (value, stamp_value) in data.iter_mut().zip(pixel) {
+                let stamp_value = (*stamp_value as f32 * intensity).min(u8::MAX as f32) as u8;
+                *value = value.saturating_add(stamp_value);
+            }
+        }
+    });
+}
+
+/// Paint the cracks a level starts with, along its cracked border and on cracked layout slots
+pub fn crack_level(
+    cracks_layer: &CracksLayer,
+    images: &mut Assets<Image>,
+    level: &LevelData,
+    cracks_data: &CracksData,
+    rng: &mut impl Rng,
+) {
+    let arena = &level.arena;
+    if level.cracked_border > 0 {
+        crack_border(cracks_layer, images, arena, cracks_data, rng);
+    }
+    for (slot, state) in level.layout_states() {
+        if let SlotState::Cracks { .. } = state {
+            let center = arena.get_slot_center(slot);
+            let direction = get_random_direction(rng);
+            crack_ice_at(
+                &center.extend(0.),
+                direction,
+                1.,
+                arena,
+                cracks_data,
+                rng,
+                cracks_layer,
+                images,
+            );
+        }
     }
 }
 
-pub fn crack_border(image: &mut Image, cracks_data: &CracksData) {
+fn crack_border(
+    cracks_layer: &CracksLayer,
+    images: &mut Assets<Image>,
+    arena: &Arena,
+    cracks_data: &CracksData,
+    rng: &mut impl Rng,
+) {
     let distance = 16.;
-    let per_row = (WINDOW_WIDTH / distance) as usize;
-    let per_column = (WINDOW_HEIGHT / distance) as usize;
+    let (width, height) = (arena.width(), arena.height());
+    let per_row = (width / distance) as usize;
+    let per_column = (height / distance) as usize;
     for index in 1..per_row {
         crack_ice_at(
             &Vec3::new(
-                index as f32 * distance - WINDOW_WIDTH / 2.,
-                (WINDOW_HEIGHT / 2.) - distance,
+                index as f32 * distance - width / 2.,
+                (height / 2.) - distance,
                 0.,
             ),
+            get_random_direction(rng),
+            1.,
+            arena,
             cracks_data,
-            image,
+            rng,
+            cracks_layer,
+            images,
         );
         crack_ice_at(
             &Vec3::new(
-                index as f32 * distance - WINDOW_WIDTH / 2.,
-                -(WINDOW_HEIGHT / 2.) + distance,
+                index as f32 * distance - width / 2.,
+                -(height / 2.) + distance,
                 0.,
             ),
+            get_random_direction(rng),
+            1.,
+            arena,
             cracks_data,
-            image,
+            rng,
+            cracks_layer,
+            images,
         );
     }
 
-    for index in 2..per_column - 1 {
+    for index in 2..per_column.saturating_sub(1) {
         crack_ice_at(
             &Vec3::new(
-                -WINDOW_WIDTH / 2. + distance,
-                index as f32 * distance - (WINDOW_HEIGHT / 2.),
+                -width / 2. + distance,
+                index as f32 * distance - (height / 2.),
                 0.,
             ),
+            get_random_direction(rng),
+            1.,
+            arena,
             cracks_data,
-            image,
+            rng,
+            cracks_layer,
+            images,
         );
         crack_ice_at(
             &Vec3::new(
-                WINDOW_WIDTH / 2. - distance,
-                index as f32 * distance - (WINDOW_HEIGHT / 2.),
+                width / 2. - distance,
+                index as f32 * distance - (height / 2.),
                 0.,
             ),
+            get_random_direction(rng),
+            1.,
+            arena,
             cracks_data,
-            image,
+            rng,
+            cracks_layer,
+            images,
         );
     }
 }
 
-struct IceGrid {
-    slots: Vec<Vec<SlotState>>,
-}
+/// A slot broke or froze over again, so the water surface around it has to be repainted
+struct WaterChangedEvent(Slot);
 
-impl Default for IceGrid {
-    fn default() -> Self {
-        let mut slots = Vec::from_iter(
-            (0..GRID_Y).map(|_| Vec::from_iter((0..GRID_X).map(|_| SlotState::Ice))),
-        );
-        slots[0] = Vec::from_iter((0..GRID_X).map(|_| SlotState::Cracks { step: 0. }));
-        slots[1] = Vec::from_iter((0..GRID_X).map(|_| SlotState::Cracks { step: 0. }));
-        slots[GRID_Y - 1] = Vec::from_iter((0..GRID_X).map(|_| SlotState::Cracks { step: 0. }));
-        slots[GRID_Y - 2] = Vec::from_iter((0..GRID_X).map(|_| SlotState::Cracks { step: 0. }));
-
-        for row in 0..GRID_Y {
-            slots[row][0] = SlotState::Cracks { step: 0. };
-            slots[row][1] = SlotState::Cracks { step: 0. };
-            slots[row][GRID_X - 1] = SlotState::Cracks { step: 0. };
-            slots[row][GRID_X - 2] = SlotState::Cracks { step: 0. };
+fn new_ice_simulation(level: &LevelData, rng: &mut impl Rng) -> IceSimulation {
+    let mut simulation = IceSimulation::new(level.arena.grid_x, level.arena.grid_y);
+    match level.thickness {
+        ThicknessMap::Uniform(thickness) => {
+            for y in 0..simulation.height() {
+                for x in 0..simulation.width() {
+                    simulation.set_thickness((x, y), thickness);
+                }
+            }
+        }
+        ThicknessMap::Generated { min, max, scale } => {
+            simulation.generate_thickness(rng, min, max, scale)
         }
-
-        IceGrid { slots }
     }
+    simulation.crack_edges(level.cracked_border);
+    for (slot, state) in level.layout_states() {
+        simulation.set_state(slot, state);
+    }
+    for (slot, terrain) in level.layout_terrain() {
+        simulation.set_terrain(slot, terrain);
+    }
+
+    simulation
 }
 
-#[derive(Copy, Clone)]
-enum SlotState {
-    Ice,
-    Cracks { step: f64 },
-    Brocken,
+/// Walkers at the edge of water slip toward it; walking away in time gets them back to safety
+fn slide_into_water(
+    time: Res<Time>,
+    arena: Res<Arena>,
+    simulation: Res<IceSimulation>,
+    mut walkers: Query<
+        (&mut Transform, &Footprint),
+        (
+            Or<(With<Player>, With<Animal>)>,
+            Without<Drowning>,
+            Without<Struggling>,
+            Without<Falling>,
+            Without<Adrift>,
+            Without<Airborne>,
+            Without<Safe>,
+        ),
+    >,
+) {
+    for (mut transform, footprint) in walkers.iter_mut() {
+        let position = transform.translation.truncate();
+        let reach = footprint.radius + arena.grid_size as f32;
+        let mut pull = Vec2::ZERO;
+        for slot in arena.slots_under(&transform.translation, reach) {
+            if !simulation.is_water(slot) {
+                continue;
+            }
+            let offset = arena.get_slot_center(slot) - position;
+            let distance = offset.length();
+            if distance > f32::EPSILON {
+                pull += offset / distance * (1. - distance / reach).max(0.);
+            }
+        }
+        if pull == Vec2::ZERO {
+            continue;
+        }
+        let movement = pull.clamp_length_max(1.) * SLIDE_SPEED * time.delta_seconds();
+        let target = arena.clamp(transform.translation + movement.extend(0.), 16.);
+        if !simulation.is_rock(arena.get_current_grid(&target)) {
+            transform.translation = target;
+        }
+    }
 }
 
-struct BreakIceEvent {
-    position: Vec2,
+/// What checking the ice grid tells the rest of the game
+#[derive(SystemParam)]
+struct IceGridEvents<'w, 's> {
+    water_changed: EventWriter<'w, 's, WaterChangedEvent>,
+    spread_cracks: EventWriter<'w, 's, SpreadCracksEvent>,
+    floe_detached: EventWriter<'w, 's, FloeDetachedEvent>,
+    player_fall: EventWriter<'w, 's, PlayerFallEvent>,
+    animal_fall: EventWriter<'w, 's, AnimalFallEvent>,
 }
 
 fn check_ice_grid(
-    player: Query<&Transform, (With<Player>, Without<Animal>, Without<Drowning>)>,
+    player: Query<
+        (&Transform, &Mass, &Footprint, &Gait),
+        (
+            With<Player>,
+            Without<Animal>,
+            Without<Airborne>,
+            Without<Drowning>,
+            Without<Struggling>,
+            Without<Adrift>,
+        ),
+    >,
     animals: Query<
-        (Entity, &Transform),
+        (Entity, &Transform, &Mass, &Footprint),
         (
             With<Animal>,
             Without<Player>,
             Without<Drowning>,
             Without<Falling>,
+            Without<Adrift>,
         ),
     >,
     time: Res<Time>,
-    mut grid: ResMut<IceGrid>,
-    mut break_ice_events: EventWriter<BreakIceEvent>,
-    mut player_fall_event: EventWriter<PlayerFallEvent>,
-    mut animal_fall_event: EventWriter<AnimalFallEvent>,
+    arena: Res<Arena>,
+    mut simulation: ResMut<IceSimulation>,
+    mut rng: ResMut<GameRng>,
+    mut events: IceGridEvents,
 ) {
-    for (entity, Transform { translation, .. }) in animals.iter() {
-        match update_and_return_ice_slot_state(
-            time.seconds_since_startup(),
-            &translation,
-            &mut grid,
-        ) {
-            WasUpdated::Yes(SlotState::Brocken) => {
-                break_ice_events.send(BreakIceEvent {
-                    position: Vec2::new(translation.x, translation.y),
-                });
-                animal_fall_event.send(AnimalFallEvent(entity));
+    let animals: Vec<(Entity, Vec<Slot>, f32)> = animals
+        .iter()
+        .map(|(entity, transform, mass, footprint)| {
+            (
+                entity,
+                arena.slots_under(&transform.translation, footprint.radius),
+                mass.0,
+            )
+        })
+        .collect();
+    let player: Option<(Vec<Slot>, f32)> =
+        player
+            .iter()
+            .next()
+            .map(|(transform, mass, footprint, gait)| {
+                (
+                    arena.slots_under(&transform.translation, footprint.radius),
+                    mass.0 * gait.load(),
+                )
+            });
+
+    // every slot under a footprint carries the full mass of its walker
+    let occupants: Vec<Occupant> = animals
+        .iter()
+        .map(|(_, slots, mass)| (slots, *mass))
+        .chain(player.iter().map(|(slots, mass)| (slots, *mass)))
+        .flat_map(|(slots, mass)| slots.iter().map(move |&slot| Occupant { slot, mass }))
+        .collect();
+
+    let mut adrift: Vec<Slot> = vec![];
+    for event in simulation.step(time.delta_seconds_f64(), &occupants, &mut rng.0) {
+        match event {
+            IceEvent::Broke(slot) => events.water_changed.send(WaterChangedEvent(slot)),
+            IceEvent::Spread(slot) => events.spread_cracks.send(SpreadCracksEvent(slot)),
+            IceEvent::Refrozen(slot) => events.water_changed.send(WaterChangedEvent(slot)),
+            IceEvent::Detached(slots) => {
+                for slot in slots.iter() {
+                    events.water_changed.send(WaterChangedEvent(*slot));
+                }
+                adrift.extend(slots.iter());
+                events.floe_detached.send(FloeDetachedEvent(slots));
             }
             _ => (),
         }
     }
-    for Transform { translation, .. } in player.iter() {
-        match update_and_return_ice_slot_state(
-            time.seconds_since_startup(),
-            &translation,
-            &mut grid,
-        ) {
-            WasUpdated::Yes(SlotState::Brocken) => {
-                break_ice_events.send(BreakIceEvent {
-                    position: Vec2::new(translation.x, translation.y),
-                });
-                // only for player
-                player_fall_event.send(PlayerFallEvent);
+
+    // covers freshly broken slots as well as walking into open water or an old hole;
+    // walkers centered on detached slots ride their floe instead
+    let falls = |slots: &[Slot]| match slots.first() {
+        Some(center) => {
+            !adrift.contains(center) && simulation.water_share(slots) > FALL_WATER_SHARE
+        }
+        None => false,
+    };
+    for (entity, slots, _) in animals.iter() {
+        if falls(slots) {
+            events.animal_fall.send(AnimalFallEvent(*entity));
+        }
+    }
+    if let Some((slots, _)) = player {
+        if falls(&slots) {
+            events.player_fall.send(PlayerFallEvent);
+        }
+    }
+}
+
+struct SpreadCracksEvent(Slot);
+
+fn paint_spread_cracks(
+    mut events: EventReader<SpreadCracksEvent>,
+    mut images: ResMut<Assets<Image>>,
+    cracks_layer: Res<CracksLayer>,
+    cracks: Res<CracksData>,
+    arena: Res<Arena>,
+    mut rng: ResMut<GameRng>,
+) {
+    for SpreadCracksEvent(slot) in events.iter() {
+        let center = arena.get_slot_center(*slot);
+        crack_ice_at(
+            &center.extend(0.),
+            get_random_direction(&mut rng.0),
+            1.,
+            &arena,
+            &cracks,
+            &mut rng.0,
+            &cracks_layer,
+            &mut images,
+        );
+    }
+}
+
+pub struct WaterLayer {
+    pub layer: Handle<Image>,
+}
+
+pub struct RefreezeTimer(pub Timer);
+
+impl Default for RefreezeTimer {
+    fn default() -> Self {
+        RefreezeTimer(Timer::from_seconds(0.5, true))
+    }
+}
+
+const WATER_COLOR: [u8; 3] = [24, 62, 128];
+const ICE_EDGE_COLOR: [u8; 3] = [214, 236, 250];
+const ICE_EDGE_WIDTH: usize = 2;
+
+/// Keep the water surface in sync with the broken slots of the simulation
+/// Changed slots are repainted together with their neighbours, so the ice edges of merging holes
+/// disappear. Every refreeze tick all water is repainted to fade with its refreeze progress.
+fn paint_water(
+    mut events: EventReader<WaterChangedEvent>,
+    mut images: ResMut<Assets<Image>>,
+    water_layer: Res<WaterLayer>,
+    simulation: Res<IceSimulation>,
+    arena: Res<Arena>,
+    mut timer: ResMut<RefreezeTimer>,
+    time: Res<Time>,
+) {
+    timer.0.tick(time.delta());
+    let mut dirty: Vec<Slot> = vec![];
+    for WaterChangedEvent((x, y)) in events.iter() {
+        for neighbour_y in y.saturating_sub(1)..=y + 1 {
+            for neighbour_x in x.saturating_sub(1)..=x + 1 {
+                if simulation.contains((neighbour_x, neighbour_y)) {
+                    dirty.push((neighbour_x, neighbour_y));
+                }
             }
-            _ => (),
         }
     }
+    if timer.0.just_finished() {
+        for region in simulation.water_regions() {
+            dirty.extend(region.iter());
+        }
+    }
+    // fetching the image mutably uploads all of it again, so only do that if a pixel changes
+    let water = images
+        .get(water_layer.layer.clone())
+        .expect("Failed to find the water layer texture");
+    dirty.sort_unstable();
+    dirty.dedup();
+    dirty.retain(|&slot| {
+        let mut changed = false;
+        water_slot_pixels(&simulation, &arena, slot, |pixel, value| {
+            changed |= water.data[pixel..pixel + DATA_PER_PIXEL] != value;
+        });
+        changed
+    });
+    if dirty.is_empty() {
+        return;
+    }
+
+    let water = images
+        .get_mut(water_layer.layer.clone())
+        .expect("Failed to find the water layer texture");
+    for slot in dirty {
+        paint_water_slot(water, &simulation, &arena, slot);
+    }
 }
 
-enum WasUpdated<T> {
-    Yes(T),
-    No(T),
+pub fn paint_water_slot(water: &mut Image, simulation: &IceSimulation, arena: &Arena, slot: Slot) {
+    water_slot_pixels(simulation, arena, slot, |pixel, value| {
+        water.data[pixel..pixel + DATA_PER_PIXEL].copy_from_slice(&value);
+    });
 }
 
-fn update_and_return_ice_slot_state(
-    seconds_since_startup: f64,
-    translation: &Vec3,
-    grid: &mut IceGrid,
-) -> WasUpdated<SlotState> {
-    let (x, y) = get_current_grid(translation);
-    let state = grid.slots[y][x];
-    if let SlotState::Ice = state {
-        let cracked_state = SlotState::Cracks {
-            step: seconds_since_startup,
-        };
-        grid.slots[y][x] = cracked_state;
+/// Call `paint` with the index and color of every pixel of `slot` in the water layer
+fn water_slot_pixels(
+    simulation: &IceSimulation,
+    arena: &Arena,
+    slot: Slot,
+    mut paint: impl FnMut(usize, [u8; DATA_PER_PIXEL]),
+) {
+    let (x, y) = slot;
+    let is_water = |offset_x: i64, offset_y: i64| {
+        let (neighbour_x, neighbour_y) = (x as i64 + offset_x, y as i64 + offset_y);
+        neighbour_x >= 0
+            && neighbour_y >= 0
+            && simulation.contains((neighbour_x as usize, neighbour_y as usize))
+            && simulation.is_water((neighbour_x as usize, neighbour_y as usize))
+    };
+    let opacity = if is_water(0, 0) {
+        1. - simulation.recovery((x, y)) as f32
+    } else {
+        0.
+    };
+
+    let grid_size = arena.grid_size;
+    let (top, left) = arena.slot_pixel_origin(slot);
+    for row in 0..grid_size {
+        for column in 0..grid_size {
+            let offset_x = if column < ICE_EDGE_WIDTH {
+                -1
+            } else if column >= grid_size - ICE_EDGE_WIDTH {
+                1
+            } else {
+                0
+            };
+            // rows grow downwards in the image, but upwards in the grid
+            let offset_y = if row < ICE_EDGE_WIDTH {
+                1
+            } else if row >= grid_size - ICE_EDGE_WIDTH {
+                -1
+            } else {
+                0
+            };
+            let on_edge =
+                !is_water(offset_x, 0) || !is_water(0, offset_y) || !is_water(offset_x, offset_y);
+            let (color, alpha) = if on_edge {
+                (ICE_EDGE_COLOR, opacity)
+            } else {
+                (WATER_COLOR, opacity * 0.9)
+            };
 
-        return WasUpdated::Yes(cracked_state);
-    } else if let SlotState::Cracks { step } = state {
-        if seconds_since_startup - step > 0.4 {
-            grid.slots[y][x] = SlotState::Brocken;
-            return WasUpdated::Yes(SlotState::Brocken);
+            let pixel = ((top + row) * arena.pixel_width() + left + column) * DATA_PER_PIXEL;
+            paint(pixel, [color[0], color[1], color[2], (alpha * 255.) as u8]);
         }
     }
+}
+
+pub struct FadeCracksTimer(pub Timer);
 
-    WasUpdated::No(state)
+impl Default for FadeCracksTimer {
+    fn default() -> Self {
+        FadeCracksTimer(Timer::from_seconds(0.5, true))
+    }
 }
 
-fn break_ice(
-    mut events: EventReader<BreakIceEvent>,
-    mut commands: Commands,
-    textures: Res<TextureAssets>,
+/// Fade the painted cracks of healing slots, so they are gone once the slot is ice again
+fn fade_cracks(
+    mut images: ResMut<Assets<Image>>,
+    cracks_layer: Res<CracksLayer>,
+    simulation: Res<IceSimulation>,
+    arena: Res<Arena>,
+    mut timer: ResMut<FadeCracksTimer>,
+    time: Res<Time>,
 ) {
-    for BreakIceEvent { position } in events.iter() {
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: textures.hole.clone(),
-                transform: Transform::from_xyz(position.x, position.y, ICE_HOLE_Z),
-                ..Default::default()
-            })
-            .insert(Level);
+    timer.0.tick(time.delta());
+    if !timer.0.just_finished() {
+        return;
+    }
+    let healed_per_tick = timer.0.duration().as_secs_f64() / simulation.config.heal_delay;
+    for y in 0..simulation.height() {
+        for x in 0..simulation.width() {
+            let factor = match simulation.state((x, y)) {
+                SlotState::Ice { .. } => 1. - healed_per_tick,
+                SlotState::Cracks { .. } => {
+                    let remaining = 1. - simulation.recovery((x, y));
+                    if remaining >= 1. {
+                        continue;
+                    }
+                    remaining / (remaining + healed_per_tick)
+                }
+                SlotState::Brocken { .. }
+                | SlotState::OpenWater
+                | SlotState::Rock
+                | SlotState::Shore => continue,
+            };
+            fade_slot(
+                &cracks_layer,
+                &mut images,
+                &arena,
+                (x, y),
+                factor.max(0.) as f32,
+            );
+        }
     }
 }
 
-fn get_current_grid(translation: &Vec3) -> (usize, usize) {
-    (
-        ((translation.x + WINDOW_WIDTH / 2.) / GRID_SIZE as f32) as usize,
-        ((translation.y + WINDOW_HEIGHT / 2.) / GRID_SIZE as f32) as usize,
-    )
+/// Only tiles with cracks in the slot get modified
+fn fade_slot(
+    cracks_layer: &CracksLayer,
+    images: &mut Assets<Image>,
+    arena: &Arena,
+    slot: Slot,
+    factor: f32,
+) {
+    let (top, left) = arena.slot_pixel_origin(slot);
+    let rect = PixelRect {
+        top,
+        left,
+        rows: arena.grid_size,
+        columns: arena.grid_size,
+    };
+    if !cracks_layer.any_cracks(images, rect) {
+        return;
+    }
+    cracks_layer.paint(images, rect, |_, _, data| {
+        for value in data.iter_mut() {
+            *value = (*value as f32 * factor) as u8;
+        }
+    });
 }
 
 #[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
 pub enum IceLabels {
+    SpawnIceSimulation,
+    SlideIntoWater,
     CheckIceGrid,
     BreakIce,
 }
 
-pub fn get_random_spawn_point(spawn_points: &mut SpawnPoints) -> Vec2 {
-    'attempt: loop {
-        let point = get_random_point(SPAWN_BORDER);
-        for spawn_point in &spawn_points.0 {
-            if spawn_point.distance(point) < 100. {
-                continue 'attempt;
-            }
+/// A random point on the ice, away from the border of the level and from earlier spawn points
+/// Falls back to a point closer to earlier spawn points when the level is too crowded, and gives
+/// up if there is no suitable ice at all.
+pub fn get_random_spawn_point(
+    spawn_points: &mut SpawnPoints,
+    level: &LevelData,
+    simulation: &IceSimulation,
+    rng: &mut impl Rng,
+) -> Option<Vec2> {
+    let mut crowded_point = None;
+    for _ in 0..SPAWN_ATTEMPTS {
+        let point = match try_random_point(&level.arena, simulation, level.spawn_border, rng) {
+            Some(point) => point,
+            None => continue,
+        };
+        if spawn_points
+            .0
+            .iter()
+            .all(|spawn_point| spawn_point.distance(point) >= SPAWN_DISTANCE)
+        {
+            spawn_points.0.push(point);
+            return Some(point);
         }
-        spawn_points.0.push(point.clone());
-        return point;
+        crowded_point.get_or_insert(point);
+    }
+    if let Some(point) = crowded_point {
+        spawn_points.0.push(point);
     }
-}
 
-pub fn get_random_point(border: f32) -> Vec2 {
-    let rand_x: f32 = random();
-    let rand_y: f32 = random();
+    crowded_point
+}
 
-    let range_x = WINDOW_WIDTH - 2. * border;
-    let range_y = WINDOW_HEIGHT - 2. * border;
+/// A random point at least `border` away from the edges of the arena on intact ice
+pub fn get_random_point(
+    arena: &Arena,
+    simulation: &IceSimulation,
+    border: f32,
+    rng: &mut impl Rng,
+) -> Option<Vec2> {
+    (0..SPAWN_ATTEMPTS).find_map(|_| try_random_point(arena, simulation, border, rng))
+}
 
-    Vec2::new(
-        range_x * rand_x + border - WINDOW_WIDTH / 2.,
-        range_y * rand_y + border - WINDOW_HEIGHT / 2.,
-    )
+fn try_random_point(
+    arena: &Arena,
+    simulation: &IceSimulation,
+    border: f32,
+    rng: &mut impl Rng,
+) -> Option<Vec2> {
+    let range_x = arena.width() - 2. * border;
+    let range_y = arena.height() - 2. * border;
+    let rand_x: f32 = rng.gen();
+    let rand_y: f32 = rng.gen();
+    let point = Vec2::new(
+        range_x * rand_x + border - arena.width() / 2.,
+        range_y * rand_y + border - arena.height() / 2.,
+    );
+    let slot = arena.get_current_grid(&point.extend(0.));
+    // cracks, even permanent ones, would break under a walker right away
+    if simulation.contains(slot)
+        && matches!(simulation.state(slot), SlotState::Ice { .. })
+        && simulation.terrain(slot) != Terrain::Slush
+    {
+        Some(point)
+    } else {
+        None
+    }
 }
 
-pub fn get_random_direction() -> Vec2 {
-    let rand: f32 = random();
+pub fn get_random_direction(rng: &mut impl Rng) -> Vec2 {
+    let rand: f32 = rng.gen();
     let rotation = Mat2::from_angle(rand * 2. * PI);
     rotation.mul_vec2(Vec2::new(1., 0.))
 }
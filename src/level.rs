@@ -0,0 +1,488 @@
+use crate::loading::LevelAssets;
+use crate::simulation::{Slot, SlotState, Terrain};
+use crate::GameState;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+
+pub struct LevelPlugin;
+
+/// This plugin loads arena descriptions from `.level.ron` files
+/// The level picked in the menu is available as [CurrentLevel] and [Arena]
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LevelData>()
+            .init_asset_loader::<LevelLoader>()
+            .insert_resource(SelectedLevel(0))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loading)
+                    .with_system(select_level.exclusive_system().at_start()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu).with_system(select_level.exclusive_system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Menu)
+                    .with_system(select_level.exclusive_system().at_start()),
+            );
+    }
+}
+
+#[derive(Clone, Deserialize, TypeUuid)]
+#[uuid = "5b7d2e0c-8f4a-4c1e-9a3d-6e2b1f0a7c93"]
+pub struct LevelData {
+    /// Shown in the menu and stored with saved runs
+    pub name: String,
+    pub arena: Arena,
+    /// Slots closer than this to the edge of the arena start out cracked
+    pub cracked_border: usize,
+    /// Rows of slots from the top of the arena to the bottom
+    /// `.` is ice, `/` cracked ice, `~` open water, `#` rock, `=` shore, `*` snow and `%` slush.
+    /// Missing rows and columns are ice.
+    #[serde(default)]
+    pub layout: Vec<String>,
+    pub thickness: ThicknessMap,
+    /// Random spawn points keep at least this distance to the edge of the arena
+    pub spawn_border: f32,
+    #[serde(default)]
+    pub player_spawn: Option<(f32, f32)>,
+    /// Used for the initial animals before falling back to random spawn points
+    #[serde(default)]
+    pub animal_spawns: Vec<(f32, f32)>,
+    pub initial_animals: usize,
+    /// Spawned one after another; the last wave repeats until the run ends
+    pub waves: Vec<AnimalWave>,
+    #[serde(default)]
+    pub goal: Goal,
+    #[serde(default)]
+    pub climate: Climate,
+    /// Sorted by time; without keyframes the difficulty stays the same for the whole run
+    #[serde(default)]
+    pub difficulty: Vec<DifficultyKeyframe>,
+}
+
+impl LevelData {
+    /// The initial state of every slot listed in the layout
+    pub fn layout_states(&self) -> Vec<(Slot, SlotState)> {
+        self.layout_slots()
+            .filter_map(|(slot, character)| match character {
+                '/' => Some((slot, SlotState::PERMANENT_CRACKS)),
+                '~' => Some((slot, SlotState::OpenWater)),
+                '#' => Some((slot, SlotState::Rock)),
+                '=' => Some((slot, SlotState::Shore)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The terrain of every slot listed in the layout that is not plain ice
+    pub fn layout_terrain(&self) -> Vec<(Slot, Terrain)> {
+        self.layout_slots()
+            .filter_map(|(slot, character)| match character {
+                '*' => Some((slot, Terrain::Snow)),
+                '%' => Some((slot, Terrain::Slush)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Fail on levels that can't be played
+    fn check(&self) -> Result<(), anyhow::Error> {
+        if self.arena.grid_size == 0 {
+            anyhow::bail!("The grid size needs to be positive");
+        }
+        if self.arena.grid_x == 0 || self.arena.grid_y == 0 {
+            anyhow::bail!("The arena needs at least one slot");
+        }
+        if let Some(spawn) = self.player_spawn {
+            if !self.arena.contains(spawn.into()) {
+                anyhow::bail!("The player spawn {:?} is outside the arena", spawn);
+            }
+        }
+        if let Some(spawn) = self
+            .animal_spawns
+            .iter()
+            .find(|&&spawn| !self.arena.contains(spawn.into()))
+        {
+            anyhow::bail!("The animal spawn {:?} is outside the arena", spawn);
+        }
+        // a wave without delay would repeat every frame
+        if let Some(index) = self.waves.iter().position(|wave| wave.delay <= 0.) {
+            anyhow::bail!("Animal wave {} needs a positive delay", index);
+        }
+
+        Ok(())
+    }
+
+    fn layout_slots(&self) -> impl Iterator<Item = (Slot, char)> + '_ {
+        let grid_x = self.arena.grid_x;
+        let grid_y = self.arena.grid_y;
+        self.layout
+            .iter()
+            .enumerate()
+            .take(grid_y)
+            .flat_map(move |(row, line)| {
+                line.chars()
+                    .enumerate()
+                    .take(grid_x)
+                    .map(move |(x, character)| ((x, grid_y - 1 - row), character))
+            })
+    }
+}
+
+/// Size of the ice grid; the arena is centered on the world origin
+#[derive(Clone, Copy, Deserialize)]
+pub struct Arena {
+    pub grid_x: usize,
+    pub grid_y: usize,
+    /// Edge length of one slot in pixels
+    pub grid_size: usize,
+}
+
+impl Arena {
+    pub fn pixel_width(&self) -> usize {
+        self.grid_x * self.grid_size
+    }
+
+    pub fn pixel_height(&self) -> usize {
+        self.grid_y * self.grid_size
+    }
+
+    pub fn width(&self) -> f32 {
+        self.pixel_width() as f32
+    }
+
+    pub fn height(&self) -> f32 {
+        self.pixel_height() as f32
+    }
+
+    pub fn get_current_grid(&self, translation: &Vec3) -> Slot {
+        (
+            ((translation.x + self.width() / 2.) / self.grid_size as f32) as usize,
+            ((translation.y + self.height() / 2.) / self.grid_size as f32) as usize,
+        )
+    }
+
+    pub fn get_slot_center(&self, (x, y): Slot) -> Vec2 {
+        Vec2::new(
+            (x as f32 + 0.5) * self.grid_size as f32 - self.width() / 2.,
+            (y as f32 + 0.5) * self.grid_size as f32 - self.height() / 2.,
+        )
+    }
+
+    /// The slot under `translation` followed by every other slot with its center in the circle
+    /// Slots outside the arena are left out, so the result is empty far off the arena.
+    pub fn slots_under(&self, translation: &Vec3, radius: f32) -> Vec<Slot> {
+        let center_x = ((translation.x + self.width() / 2.) / self.grid_size as f32).floor() as i64;
+        let center_y =
+            ((translation.y + self.height() / 2.) / self.grid_size as f32).floor() as i64;
+        let reach = (radius / self.grid_size as f32).ceil() as i64;
+        let mut slots: Vec<Slot> = self.slot_at(center_x, center_y).into_iter().collect();
+        for offset_y in -reach..=reach {
+            for offset_x in -reach..=reach {
+                if offset_x == 0 && offset_y == 0 {
+                    continue;
+                }
+                if let Some(slot) = self.slot_at(center_x + offset_x, center_y + offset_y) {
+                    if self.get_slot_center(slot).distance(translation.truncate()) <= radius {
+                        slots.push(slot);
+                    }
+                }
+            }
+        }
+
+        slots
+    }
+
+    fn slot_at(&self, x: i64, y: i64) -> Option<Slot> {
+        if x < 0 || y < 0 || x >= self.grid_x as i64 || y >= self.grid_y as i64 {
+            return None;
+        }
+
+        Some((x as usize, y as usize))
+    }
+
+    fn contains(&self, position: Vec2) -> bool {
+        position.x.abs() < self.width() / 2. && position.y.abs() < self.height() / 2.
+    }
+
+    /// Pixel row and column of the top left corner of `slot` in arena sized images
+    pub fn slot_pixel_origin(&self, (x, y): Slot) -> (usize, usize) {
+        (
+            self.pixel_height() - (y + 1) * self.grid_size,
+            x * self.grid_size,
+        )
+    }
+
+    /// Keep a position this far away from the edges of the arena
+    pub fn clamp(&self, translation: Vec3, margin: f32) -> Vec3 {
+        translation.clamp(
+            Vec3::new(
+                -self.width() / 2. + margin,
+                -self.height() / 2. + margin,
+                translation.z,
+            ),
+            Vec3::new(
+                self.width() / 2. - margin,
+                self.height() / 2. - margin,
+                translation.z,
+            ),
+        )
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub enum ThicknessMap {
+    Uniform(f32),
+    /// Smooth noise between `min` and `max`, varying over roughly `scale` slots
+    Generated {
+        min: f32,
+        max: f32,
+        scale: usize,
+    },
+}
+
+/// What a run is about besides not falling in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Goal {
+    /// The score grows for as long as the player stays dry
+    #[default]
+    Survive,
+    /// Walking ashore banks the score; animals reaching the shore before that are rescued
+    ReachShore,
+}
+
+/// How the temperature develops over a run
+#[derive(Clone, Deserialize)]
+pub struct Climate {
+    /// Degrees Celsius at the start of the run, in the middle of the night
+    pub start: f32,
+    /// Degrees per minute the days get warmer
+    pub warming: f32,
+    /// Degrees between the coldest and the warmest time of a day
+    pub day_swing: f32,
+    /// Seconds from one night to the next
+    pub day_length: f32,
+}
+
+impl Default for Climate {
+    fn default() -> Self {
+        Climate {
+            start: -8.,
+            warming: 1.5,
+            day_swing: 4.,
+            day_length: 90.,
+        }
+    }
+}
+
+/// Difficulty factors reached after `time` seconds; values in between are interpolated
+#[derive(Clone, Deserialize)]
+pub struct DifficultyKeyframe {
+    pub time: f32,
+    /// Factor on how fast the animal waves come
+    pub spawn_rate: f32,
+    pub animal_speed: f32,
+    /// Factor on how fast the ice gives in
+    pub fragility: f32,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct AnimalWave {
+    /// Seconds after the previous wave
+    pub delay: f32,
+    pub count: usize,
+}
+
+pub struct CurrentLevel(pub LevelData);
+
+/// Index into [LevelAssets::all] of the level to play next
+pub struct SelectedLevel(pub usize);
+
+/// The levels offered in the menu, in order
+pub fn all_levels<'a>(
+    level_assets: &LevelAssets,
+    levels: &'a Assets<LevelData>,
+) -> Vec<&'a LevelData> {
+    level_assets
+        .all()
+        .iter()
+        .map(|handle| levels.get(handle).expect("Failed to find a level"))
+        .collect()
+}
+
+/// Make the selected level the current one, unless it already is
+fn select_level(world: &mut World) {
+    let index = world
+        .get_resource::<SelectedLevel>()
+        .expect("Failed to get the selected level")
+        .0;
+    let level = {
+        let level_assets = world
+            .get_resource::<LevelAssets>()
+            .expect("Failed to get level assets");
+        let levels = world
+            .get_resource::<Assets<LevelData>>()
+            .expect("No level assets");
+        let all = all_levels(level_assets, levels);
+        let level = all[index % all.len()];
+        if let Some(current) = world.get_resource::<CurrentLevel>() {
+            if current.0.name == level.name {
+                return;
+            }
+        }
+        level.clone()
+    };
+
+    world.insert_resource(level.arena);
+    world.insert_resource(CurrentLevel(level));
+}
+
+#[derive(Default)]
+struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let level: LevelData = ron::de::from_bytes(bytes)?;
+            level.check()?;
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x3 slots of 10 pixels, so slot (0, 0) is centered at (-15, -10)
+    fn arena() -> Arena {
+        Arena {
+            grid_x: 4,
+            grid_y: 3,
+            grid_size: 10,
+        }
+    }
+
+    fn level_data() -> LevelData {
+        ron::de::from_str(
+            "(
+                name: \"Test\",
+                arena: (grid_x: 4, grid_y: 3, grid_size: 10),
+                cracked_border: 0,
+                thickness: Uniform(1.),
+                spawn_border: 0.,
+                initial_animals: 0,
+                waves: [(delay: 1., count: 1)],
+            )",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn layout_states_run_from_the_top_row_down() {
+        let mut level = level_data();
+        level.layout = vec![
+            "#=..".to_string(),
+            "/~*%".to_string(),
+            "....#".to_string(),
+            "####".to_string(),
+        ];
+
+        assert_eq!(
+            level.layout_states(),
+            vec![
+                ((0, 2), SlotState::Rock),
+                ((1, 2), SlotState::Shore),
+                ((0, 1), SlotState::PERMANENT_CRACKS),
+                ((1, 1), SlotState::OpenWater),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_accepts_spawns_in_the_arena() {
+        let mut level = level_data();
+        level.player_spawn = Some((15., -10.));
+        level.animal_spawns = vec![(-19., 14.)];
+
+        assert!(level.check().is_ok());
+    }
+
+    #[test]
+    fn check_rejects_empty_arenas() {
+        let mut level = level_data();
+        level.arena.grid_size = 0;
+        assert!(level.check().is_err());
+
+        let mut level = level_data();
+        level.arena.grid_y = 0;
+        assert!(level.check().is_err());
+    }
+
+    #[test]
+    fn check_rejects_spawns_outside_the_arena() {
+        let mut level = level_data();
+        level.player_spawn = Some((20., 0.));
+        assert!(level.check().is_err());
+
+        let mut level = level_data();
+        level.animal_spawns = vec![(0., 0.), (0., -15.)];
+        assert!(level.check().is_err());
+    }
+
+    #[test]
+    fn check_rejects_waves_without_delay() {
+        let mut level = level_data();
+        level.waves.push(AnimalWave {
+            delay: 0.,
+            count: 2,
+        });
+
+        assert!(level.check().is_err());
+    }
+
+    #[test]
+    fn slots_under_starts_with_the_center_slot() {
+        let slots = arena().slots_under(&Vec3::new(0., 0., 0.), 4.);
+
+        assert_eq!(slots, vec![(2, 1)]);
+    }
+
+    #[test]
+    fn slots_under_stops_at_corners() {
+        let arena = arena();
+
+        assert_eq!(
+            arena.slots_under(&Vec3::new(-15., -10., 0.), 10.),
+            vec![(0, 0), (1, 0), (0, 1)]
+        );
+        assert_eq!(
+            arena.slots_under(&Vec3::new(15., 10., 0.), 10.),
+            vec![(3, 2), (3, 1), (2, 2)]
+        );
+    }
+
+    #[test]
+    fn slots_under_leaves_out_a_center_off_the_arena() {
+        let arena = arena();
+
+        assert_eq!(
+            arena.slots_under(&Vec3::new(-22., -10., 0.), 10.),
+            vec![(0, 0)]
+        );
+        assert!(arena.slots_under(&Vec3::new(-25., 0., 0.), 4.).is_empty());
+        assert!(arena.slots_under(&Vec3::new(0., 100., 0.), 4.).is_empty());
+    }
+}
@@ -2,11 +2,25 @@ mod actions;
 mod animal;
 mod animate;
 mod audio;
+mod camera;
+mod climb;
 mod countdown;
+#[cfg(feature = "dev")]
+mod debug;
+mod difficulty;
+mod floe;
+mod gamepad;
 mod ice;
+mod level;
 mod loading;
 mod menu;
 mod player;
+mod rng;
+mod save;
+mod shore;
+pub mod simulation;
+mod stamps;
+mod temperature;
 mod ui;
 
 use crate::actions::ActionsPlugin;
@@ -14,11 +28,24 @@ use crate::audio::InternalAudioPlugin;
 use crate::loading::LoadingPlugin;
 use crate::menu::MenuPlugin;
 use crate::player::PlayerPlugin;
+use crate::rng::RngPlugin;
+use crate::save::SavePlugin;
+use crate::shore::ShorePlugin;
+use crate::stamps::StampsPlugin;
+use crate::temperature::TemperaturePlugin;
 
 use crate::animal::AnimalPlugin;
 use crate::animate::AnimatePlugin;
+use crate::camera::CameraPlugin;
+use crate::climb::ClimbPlugin;
 use crate::countdown::CountdownPlugin;
+#[cfg(feature = "dev")]
+use crate::debug::DebugPlugin;
+use crate::difficulty::DifficultyPlugin;
+use crate::floe::FloePlugin;
+use crate::gamepad::GamepadPlugin;
 use crate::ice::IcePlugin;
+use crate::level::LevelPlugin;
 use crate::ui::UiPlugin;
 use bevy::app::App;
 #[cfg(debug_assertions)]
@@ -42,21 +69,36 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_state(GameState::Loading)
+            .add_plugin(RngPlugin)
             .add_plugin(LoadingPlugin)
+            .add_plugin(LevelPlugin)
+            .add_plugin(StampsPlugin)
+            .add_plugin(CameraPlugin)
             .add_plugin(MenuPlugin)
             .add_plugin(UiPlugin)
             .add_plugin(IcePlugin)
+            .add_plugin(FloePlugin)
             .add_plugin(ActionsPlugin)
+            .add_plugin(GamepadPlugin)
             .add_plugin(InternalAudioPlugin)
             .add_plugin(PlayerPlugin)
             .add_plugin(AnimalPlugin)
             .add_plugin(AnimatePlugin)
-            .add_plugin(CountdownPlugin);
+            .add_plugin(CountdownPlugin)
+            .add_plugin(ClimbPlugin)
+            .add_plugin(ShorePlugin)
+            .add_plugin(TemperaturePlugin)
+            .add_plugin(DifficultyPlugin)
+            .add_plugin(SavePlugin);
 
-        app.add_system_set(SystemSet::on_exit(GameState::Loading).with_system(setup_cameras))
-            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(despawn_level))
+        app.add_system_set(SystemSet::on_exit(GameState::Playing).with_system(despawn_level))
             .add_system_set(SystemSet::on_enter(GameState::Restart).with_system(restart));
 
+        #[cfg(feature = "dev")]
+        {
+            app.add_plugin(DebugPlugin);
+        }
+
         #[cfg(debug_assertions)]
         {
             app.add_plugin(FrameTimeDiagnosticsPlugin::default())
@@ -68,11 +110,6 @@ impl Plugin for GamePlugin {
 #[derive(Component)]
 pub struct Level;
 
-fn setup_cameras(mut commands: Commands) {
-    commands.spawn_bundle(UiCameraBundle::default());
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-}
-
 fn restart(mut state: ResMut<State<GameState>>) {
     state.set(GameState::Countdown).unwrap();
 }
@@ -1,10 +1,14 @@
-use crate::ice::crack_border;
+use crate::ice::{crack_level, DATA_PER_PIXEL};
+use crate::level::{CurrentLevel, LevelData};
+use crate::rng::GameRng;
+use crate::stamps::{CrackStamp, CrackStamps};
 use crate::GameState;
 use bevy::prelude::*;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 use bevy_asset_loader::{AssetCollection, AssetLoader};
 use bevy_kira_audio::AudioSource;
-use rand::random;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
 
 pub struct LoadingPlugin;
 
@@ -17,14 +21,163 @@ impl Plugin for LoadingPlugin {
             .with_collection::<FontAssets>()
             .with_collection::<AudioAssets>()
             .with_collection::<TextureAssets>()
+            .with_collection::<LevelAssets>()
             .init_resource::<CracksData>()
             .continue_to_state(GameState::Menu)
             .build(app);
     }
 }
 
+const CRACKS_TILE_SIZE: usize = 128;
+
+/// The painted cracks, split into square tiles
+/// Painting only modifies the tiles it touches, so only those get uploaded to the GPU again.
 pub struct CracksLayer {
-    pub layer: Handle<Image>,
+    tiles: Vec<Handle<Image>>,
+    tiles_x: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Pixel rectangle in arena sized images
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelRect {
+    pub top: usize,
+    pub left: usize,
+    pub rows: usize,
+    pub columns: usize,
+}
+
+impl CracksLayer {
+    fn new(images: &mut Assets<Image>, width: usize, height: usize) -> Self {
+        let tiles_x = width.div_ceil(CRACKS_TILE_SIZE);
+        let tiles_y = height.div_ceil(CRACKS_TILE_SIZE);
+        let mut layer = CracksLayer {
+            tiles: vec![],
+            tiles_x,
+            width,
+            height,
+        };
+        for index in 0..tiles_x * tiles_y {
+            let rect = layer.tile_rect(index);
+            layer.tiles.push(images.add(Image::new_fill(
+                Extent3d {
+                    width: rect.columns as u32,
+                    height: rect.rows as u32,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                &[0u8, 0u8, 0u8, 0u8],
+                TextureFormat::Rgba8UnormSrgb,
+            )));
+        }
+
+        layer
+    }
+
+    fn tile_rect(&self, index: usize) -> PixelRect {
+        let top = index / self.tiles_x * CRACKS_TILE_SIZE;
+        let left = index % self.tiles_x * CRACKS_TILE_SIZE;
+        PixelRect {
+            top,
+            left,
+            rows: CRACKS_TILE_SIZE.min(self.height - top),
+            columns: CRACKS_TILE_SIZE.min(self.width - left),
+        }
+    }
+
+    /// Every tile together with the part of the layer it covers
+    pub fn tiles(&self) -> impl Iterator<Item = (&Handle<Image>, PixelRect)> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(move |(index, tile)| (tile, self.tile_rect(index)))
+    }
+
+    /// Indices of the tiles overlapping `rect` after clipping it to the layer
+    fn overlapping(&self, rect: PixelRect) -> Vec<(usize, PixelRect)> {
+        let bottom = (rect.top + rect.rows).min(self.height);
+        let right = (rect.left + rect.columns).min(self.width);
+        if rect.top >= bottom || rect.left >= right {
+            return vec![];
+        }
+        let mut tiles = vec![];
+        for tile_y in rect.top / CRACKS_TILE_SIZE..=(bottom - 1) / CRACKS_TILE_SIZE {
+            for tile_x in rect.left / CRACKS_TILE_SIZE..=(right - 1) / CRACKS_TILE_SIZE {
+                let index = tile_y * self.tiles_x + tile_x;
+                let tile = self.tile_rect(index);
+                let top = rect.top.max(tile.top);
+                let left = rect.left.max(tile.left);
+                tiles.push((
+                    index,
+                    PixelRect {
+                        top,
+                        left,
+                        rows: bottom.min(tile.top + tile.rows) - top,
+                        columns: right.min(tile.left + tile.columns) - left,
+                    },
+                ));
+            }
+        }
+
+        tiles
+    }
+
+    pub fn pixel<'a>(&self, images: &'a Assets<Image>, row: usize, column: usize) -> &'a [u8] {
+        let index = row / CRACKS_TILE_SIZE * self.tiles_x + column / CRACKS_TILE_SIZE;
+        let tile = self.tile_rect(index);
+        let image = images
+            .get(self.tiles[index].clone())
+            .expect("Failed to find a cracks tile");
+        let pixel = ((row - tile.top) * tile.columns + column - tile.left) * DATA_PER_PIXEL;
+
+        &image.data[pixel..pixel + DATA_PER_PIXEL]
+    }
+
+    /// Whether any pixel in `rect` has cracks painted on it
+    pub fn any_cracks(&self, images: &Assets<Image>, rect: PixelRect) -> bool {
+        for (index, part) in self.overlapping(rect) {
+            let tile = self.tile_rect(index);
+            let image = images
+                .get(self.tiles[index].clone())
+                .expect("Failed to find a cracks tile");
+            for row in part.top..part.top + part.rows {
+                let start =
+                    ((row - tile.top) * tile.columns + part.left - tile.left) * DATA_PER_PIXEL;
+                if image.data[start..start + part.columns * DATA_PER_PIXEL]
+                    .iter()
+                    .any(|value| *value > 0)
+                {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Call `paint` with the row, column and data of every pixel in `rect`
+    /// Only the tiles overlapping `rect` are modified.
+    pub fn paint(
+        &self,
+        images: &mut Assets<Image>,
+        rect: PixelRect,
+        mut paint: impl FnMut(usize, usize, &mut [u8]),
+    ) {
+        for (index, part) in self.overlapping(rect) {
+            let tile = self.tile_rect(index);
+            let image = images
+                .get_mut(self.tiles[index].clone())
+                .expect("Failed to find a cracks tile");
+            for row in part.top..part.top + part.rows {
+                for column in part.left..part.left + part.columns {
+                    let pixel =
+                        ((row - tile.top) * tile.columns + column - tile.left) * DATA_PER_PIXEL;
+                    paint(row, column, &mut image.data[pixel..pixel + DATA_PER_PIXEL]);
+                }
+            }
+        }
+    }
 }
 
 impl FromWorld for CracksLayer {
@@ -32,103 +185,51 @@ impl FromWorld for CracksLayer {
         let cell = world.cell();
         let mut images = cell.get_resource_mut::<Assets<Image>>().unwrap();
         let cracks_data = cell.get_resource::<CracksData>().unwrap();
-        let mut image = Image::new_fill(
-            Extent3d {
-                width: 800,
-                height: 600,
-                depth_or_array_layers: 1,
-            },
-            TextureDimension::D2,
-            &[0u8, 0u8, 0u8, 0u8],
-            TextureFormat::Rgba8UnormSrgb,
+        let mut rng = cell.get_resource_mut::<GameRng>().unwrap();
+        let level = cell.get_resource::<CurrentLevel>().unwrap();
+        let layer = CracksLayer::new(
+            &mut images,
+            level.0.arena.pixel_width(),
+            level.0.arena.pixel_height(),
         );
-        crack_border(&mut image, &cracks_data);
-        let layer = images.add(image);
+        crack_level(&layer, &mut images, &level.0, &cracks_data, &mut rng.0);
 
-        CracksLayer { layer }
+        layer
     }
 }
 
+/// The crack stamps of the loaded library, ready to be picked by weight
 pub struct CracksData {
-    pub cracks_0: Vec<PixelData>,
-    pub cracks_1: Vec<PixelData>,
+    stamps: Vec<CrackStamp>,
+    weights: WeightedIndex<f32>,
 }
 
 impl CracksData {
-    pub fn random(&self) -> &Vec<PixelData> {
-        let weight: f32 = random();
-
-        if weight > 0.5 {
-            &self.cracks_0
-        } else {
-            &self.cracks_1
-        }
+    pub fn random(&self, rng: &mut impl Rng) -> &CrackStamp {
+        &self.stamps[self.weights.sample(rng)]
     }
 }
 
-pub struct PixelData {
-    pub row: usize,
-    pub column: usize,
-    pub offset: usize,
-    pub data: u8,
-}
-
 impl FromWorld for CracksData {
     fn from_world(world: &mut World) -> Self {
         let cell = world.cell();
         let texture_assets = cell
             .get_resource::<TextureAssets>()
             .expect("Failed to get texture assets");
-        let images = cell
-            .get_resource::<Assets<Image>>()
-            .expect("No image assets");
-        let cracks_0_image = images
-            .get(texture_assets.cracks_0.clone())
-            .expect("No cracks image");
-        let cracks_1_image = images
-            .get(texture_assets.cracks_1.clone())
-            .expect("No cracks image");
+        let libraries = cell
+            .get_resource::<Assets<CrackStamps>>()
+            .expect("No crack stamp assets");
+        let library = libraries
+            .get(texture_assets.cracks.clone())
+            .expect("No crack stamps");
 
         CracksData {
-            cracks_0: filter_image_data(cracks_0_image, 32, 32, 4),
-            cracks_1: filter_image_data(cracks_1_image, 32, 32, 4),
+            stamps: library.stamps.clone(),
+            weights: WeightedIndex::new(&library.weights).expect("Invalid crack stamp weights"),
         }
     }
 }
 
-fn filter_image_data(
-    image: &Image,
-    rows: usize,
-    columns: usize,
-    data_per_pixel: usize,
-) -> Vec<PixelData> {
-    let mut data = vec![];
-
-    for row in 0..rows {
-        for column in 0..columns {
-            for offset in 0..data_per_pixel {
-                let value = image.data[(row * columns + column) * data_per_pixel + offset];
-                if value > 0 {
-                    data.push(PixelData {
-                        row,
-                        column,
-                        offset,
-                        data: value,
-                    })
-                }
-            }
-        }
-    }
-
-    info!(
-        "Reduced pixeldata from {} to {}",
-        rows * columns * data_per_pixel,
-        data.len()
-    );
-
-    data
-}
-
 #[derive(AssetCollection)]
 pub struct FontAssets {
     #[asset(path = "fonts/FiraSans-Bold.ttf")]
@@ -145,6 +246,27 @@ pub struct AudioAssets {
     pub breaking_ice: Handle<AudioSource>,
 }
 
+#[derive(AssetCollection)]
+pub struct LevelAssets {
+    #[asset(path = "levels/default.level.ron")]
+    pub default: Handle<LevelData>,
+    #[asset(path = "levels/crossing.level.ron")]
+    pub crossing: Handle<LevelData>,
+    #[asset(path = "levels/wilds.level.ron")]
+    pub wilds: Handle<LevelData>,
+}
+
+impl LevelAssets {
+    /// Every level in the order the menu offers them
+    pub fn all(&self) -> Vec<Handle<LevelData>> {
+        vec![
+            self.default.clone(),
+            self.crossing.clone(),
+            self.wilds.clone(),
+        ]
+    }
+}
+
 #[derive(AssetCollection)]
 pub struct TextureAssets {
     #[asset(texture_atlas(tile_size_x = 32., tile_size_y = 32., columns = 4, rows = 1))]
@@ -157,14 +279,98 @@ pub struct TextureAssets {
     pub animal: Handle<Image>,
     #[asset(path = "textures/ice.png")]
     pub ice: Handle<Image>,
-    #[asset(path = "textures/hole.png")]
-    pub hole: Handle<Image>,
-    #[asset(path = "textures/cracks_0.png")]
-    pub cracks_0: Handle<Image>,
-    #[asset(path = "textures/cracks_1.png")]
-    pub cracks_1: Handle<Image>,
+    #[asset(path = "textures/cracks.stamps.ron")]
+    pub cracks: Handle<CrackStamps>,
     #[asset(path = "textures/end.png")]
     pub end: Handle<Image>,
     #[asset(path = "textures/info.png")]
     pub info: Handle<Image>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+
+    fn images() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Image>();
+
+        app
+    }
+
+    fn rect(top: usize, left: usize, rows: usize, columns: usize) -> PixelRect {
+        PixelRect {
+            top,
+            left,
+            rows,
+            columns,
+        }
+    }
+
+    #[test]
+    fn new_covers_the_layer_with_tiles() {
+        let mut app = images();
+        let mut images = app.world.get_resource_mut::<Assets<Image>>().unwrap();
+        let layer = CracksLayer::new(&mut images, 300, 200);
+        let tiles: Vec<_> = layer.tiles().map(|(_, rect)| rect).collect();
+
+        assert_eq!(
+            tiles,
+            vec![
+                rect(0, 0, 128, 128),
+                rect(0, 128, 128, 128),
+                rect(0, 256, 128, 44),
+                rect(128, 0, 72, 128),
+                rect(128, 128, 72, 128),
+                rect(128, 256, 72, 44),
+            ]
+        );
+        for (tile, rect) in layer.tiles() {
+            let size = images.get(tile.clone()).unwrap().texture_descriptor.size;
+            assert_eq!(
+                (size.width as usize, size.height as usize),
+                (rect.columns, rect.rows)
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping_splits_rects_along_tiles_and_clips_them() {
+        let mut app = images();
+        let mut images = app.world.get_resource_mut::<Assets<Image>>().unwrap();
+        let layer = CracksLayer::new(&mut images, 300, 200);
+
+        assert_eq!(
+            layer.overlapping(rect(100, 250, 50, 100)),
+            vec![
+                (1, rect(100, 250, 28, 6)),
+                (2, rect(100, 256, 28, 44)),
+                (4, rect(128, 250, 22, 6)),
+                (5, rect(128, 256, 22, 44)),
+            ]
+        );
+        assert_eq!(
+            layer.overlapping(rect(10, 10, 5, 5)),
+            vec![(0, rect(10, 10, 5, 5))]
+        );
+        assert!(layer.overlapping(rect(200, 0, 10, 10)).is_empty());
+        assert!(layer.overlapping(rect(0, 300, 10, 10)).is_empty());
+    }
+
+    #[test]
+    fn painted_pixels_show_up_as_cracks() {
+        let mut app = images();
+        let mut images = app.world.get_resource_mut::<Assets<Image>>().unwrap();
+        let layer = CracksLayer::new(&mut images, 300, 200);
+        layer.paint(&mut images, rect(130, 260, 1, 1), |_, _, pixel| {
+            pixel[3] = 255
+        });
+
+        assert_eq!(layer.pixel(&images, 130, 260), &[0, 0, 0, 255]);
+        assert!(layer.any_cracks(&images, rect(100, 250, 50, 50)));
+        assert!(!layer.any_cracks(&images, rect(0, 0, 130, 300)));
+    }
+}
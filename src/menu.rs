@@ -1,4 +1,8 @@
-use crate::loading::{FontAssets, TextureAssets};
+use crate::gamepad::GamepadLabels;
+use crate::level::{all_levels, LevelData, SelectedLevel};
+use crate::loading::{FontAssets, LevelAssets, TextureAssets};
+use crate::rng::GameSeed;
+use crate::save::{delete_snapshot, PendingSnapshot, SavedRun};
 use crate::GameState;
 use bevy::prelude::*;
 
@@ -10,7 +14,12 @@ impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ButtonColors>()
             .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(setup_menu))
-            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(click_play_button))
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(click_play_button.after(GamepadLabels::PressButtons))
+                    .with_system(click_level_button.after(GamepadLabels::PressButtons))
+                    .with_system(edit_seed),
+            )
             .add_system_set(SystemSet::on_exit(GameState::Menu).with_system(remove_menu));
     }
 }
@@ -34,34 +43,78 @@ fn setup_menu(
     font_assets: Res<FontAssets>,
     button_colors: Res<ButtonColors>,
     textures: Res<TextureAssets>,
+    seed: Res<GameSeed>,
+    saved_run: Res<SavedRun>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<LevelData>>,
+    selected_level: Res<SelectedLevel>,
 ) {
+    let mut buttons = vec![("Walk on thin ice".to_string(), MenuButton::Play)];
+    if saved_run.0.is_some() {
+        buttons.push(("Continue".to_string(), MenuButton::Continue));
+    }
+    let all = all_levels(&level_assets, &levels);
+    if all.len() > 1 {
+        buttons.push((
+            level_text(all[selected_level.0 % all.len()]),
+            MenuButton::Level,
+        ));
+    }
     commands
-        .spawn_bundle(ButtonBundle {
+        .spawn_bundle(NodeBundle {
             style: Style {
-                size: Size::new(Val::Px(300.0), Val::Px(50.0)),
-                margin: Rect::all(Val::Auto),
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 ..Default::default()
             },
-            color: button_colors.normal,
+            color: UiColor(Color::NONE),
             ..Default::default()
         })
+        .insert(Menu)
         .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text {
-                    sections: vec![TextSection {
-                        value: "Walk on thin ice".to_string(),
-                        style: TextStyle {
-                            font: font_assets.fira_sans.clone(),
-                            font_size: 40.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
+            for (label, kind) in buttons {
+                let mut button = parent.spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(300.0), Val::Px(50.0)),
+                        margin: Rect::all(Val::Px(10.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: button_colors.normal,
+                    ..Default::default()
+                });
+                button.with_children(|parent| {
+                    let mut text = parent.spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: label,
+                                style: TextStyle {
+                                    font: font_assets.fira_sans.clone(),
+                                    font_size: 40.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            }],
+                            alignment: Default::default(),
                         },
-                    }],
-                    alignment: Default::default(),
-                },
-                ..Default::default()
-            });
+                        ..Default::default()
+                    });
+                    if kind == MenuButton::Level {
+                        text.insert(LevelText);
+                    }
+                });
+                match kind {
+                    MenuButton::Play => (),
+                    MenuButton::Continue => {
+                        button.insert(ContinueButton);
+                    }
+                    MenuButton::Level => {
+                        button.insert(LevelButton);
+                    }
+                }
+            }
         });
     commands
         .spawn_bundle(SpriteBundle {
@@ -70,23 +123,137 @@ fn setup_menu(
             ..SpriteBundle::default()
         })
         .insert(Menu);
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: seed_text(seed.value),
+                    style: TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 20.0,
+                        color: Color::rgb_u8(34, 32, 52),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(SeedText { edited: false })
+        .insert(Menu);
 }
 
 #[derive(Component)]
 struct Menu;
 
+#[derive(Component)]
+struct SeedText {
+    /// The player typed since the menu opened; the first digit replaces the shown seed
+    edited: bool,
+}
+
+#[derive(Component)]
+struct ContinueButton;
+
+/// Switches to the next level
+#[derive(Component)]
+struct LevelButton;
+
+#[derive(Component)]
+struct LevelText;
+
+#[derive(Clone, Copy, PartialEq)]
+enum MenuButton {
+    Play,
+    Continue,
+    Level,
+}
+
+fn level_text(level: &LevelData) -> String {
+    format!("Level: {}", level.name)
+}
+
+pub fn seed_text(seed: u64) -> String {
+    format!("Seed: {}", seed)
+}
+
+fn edit_seed(
+    mut characters: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut seed: ResMut<GameSeed>,
+    mut seed_text_query: Query<(&mut Text, &mut SeedText)>,
+) {
+    let (mut text, mut seed_field) = seed_text_query.single_mut();
+    let mut new_seed = seed.value;
+    for character in characters.iter() {
+        if let Some(digit) = character.char.to_digit(10) {
+            if !seed_field.edited {
+                new_seed = 0;
+                seed_field.edited = true;
+            }
+            new_seed = new_seed
+                .checked_mul(10)
+                .and_then(|value| value.checked_add(digit as u64))
+                .unwrap_or(new_seed);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        new_seed /= 10;
+        seed_field.edited = true;
+    }
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        new_seed = 0;
+        seed_field.edited = true;
+    }
+    if new_seed != seed.value {
+        seed.value = new_seed;
+        seed.explicit = true;
+        text.sections[0].value = seed_text(new_seed);
+    }
+}
+
 fn click_play_button(
     mut commands: Commands,
     button_colors: Res<ButtonColors>,
     mut state: ResMut<State<GameState>>,
+    mut seed: ResMut<GameSeed>,
+    mut saved_run: ResMut<SavedRun>,
+    mut pending_snapshot: ResMut<PendingSnapshot>,
+    level_selection: (
+        Res<LevelAssets>,
+        Res<Assets<LevelData>>,
+        ResMut<SelectedLevel>,
+    ),
     mut interaction_query: Query<
-        (Entity, &Interaction, &mut UiColor),
-        (Changed<Interaction>, With<Button>),
+        (Entity, &Interaction, &mut UiColor, Option<&ContinueButton>),
+        (Changed<Interaction>, With<Button>, Without<LevelButton>),
     >,
 ) {
-    for (button, interaction, mut color) in interaction_query.iter_mut() {
+    let (level_assets, level_data, mut selected_level) = level_selection;
+    for (button, interaction, mut color, continue_button) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Clicked => {
+                if continue_button.is_some() {
+                    if let Some(snapshot) = saved_run.0.take() {
+                        seed.value = snapshot.seed;
+                        if let Some(index) = all_levels(&level_assets, &level_data)
+                            .iter()
+                            .position(|level| level.name == snapshot.level)
+                        {
+                            selected_level.0 = index;
+                        }
+                        pending_snapshot.0 = Some(snapshot);
+                        delete_snapshot();
+                    }
+                }
                 commands.entity(button).despawn_recursive();
                 state.set(GameState::Countdown).unwrap();
             }
@@ -100,6 +267,36 @@ fn click_play_button(
     }
 }
 
+fn click_level_button(
+    button_colors: Res<ButtonColors>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<LevelData>>,
+    mut selected_level: ResMut<SelectedLevel>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor),
+        (Changed<Interaction>, With<LevelButton>),
+    >,
+    mut level_text_query: Query<&mut Text, With<LevelText>>,
+) {
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                let all = all_levels(&level_assets, &levels);
+                selected_level.0 = (selected_level.0 + 1) % all.len();
+                for mut text in level_text_query.iter_mut() {
+                    text.sections[0].value = level_text(all[selected_level.0]);
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered;
+            }
+            Interaction::None => {
+                *color = button_colors.normal;
+            }
+        }
+    }
+}
+
 fn remove_menu(mut commands: Commands, menu_elements: Query<Entity, With<Menu>>) {
     for entity in menu_elements.iter() {
         commands.entity(entity).despawn_recursive();
@@ -1,11 +1,18 @@
 use crate::actions::Actions;
-use crate::animal::Walking;
-use crate::animate::AnimationTimer;
+use crate::animal::{Footprint, Mass, Walking};
+use crate::animate::{Airborne, AnimationTimer, JUMP_SECONDS};
+use crate::climb::Struggling;
+use crate::floe::Adrift;
 use crate::ice::{get_random_direction, get_random_spawn_point, IceLabels, SpawnPoints};
+use crate::level::{Arena, CurrentLevel};
 use crate::loading::TextureAssets;
-use crate::{GameState, Level, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::rng::GameRng;
+use crate::shore::Safe;
+use crate::simulation::IceSimulation;
+use crate::{GameState, Level};
 use bevy::math::Mat2;
 use bevy::prelude::*;
+use std::time::Duration;
 
 pub const PLAYER_Z: f32 = 5.;
 
@@ -19,31 +26,130 @@ pub struct Player;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<PlayerFallEvent>()
+            .add_event::<PlayerDrownEvent>()
             .add_event::<AnimalFallEvent>()
-            .add_system_set(SystemSet::on_enter(GameState::Countdown).with_system(spawn_player))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Countdown)
+                    .with_system(spawn_player.label(PlayerLabels::SpawnPlayer)),
+            )
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
-                    .with_system(move_player.before(IceLabels::CheckIceGrid)),
+                    .with_system(set_gait.label(PlayerLabels::SetGait))
+                    .with_system(jump.before(IceLabels::CheckIceGrid))
+                    .with_system(
+                        move_player
+                            .label(PlayerLabels::MovePlayer)
+                            .after(PlayerLabels::SetGait)
+                            .before(IceLabels::CheckIceGrid),
+                    ),
             );
     }
 }
 
+/// The player broke through the ice and tries to climb back out
 pub struct PlayerFallEvent;
+/// The player did not make it out of the water; the run is over
+pub struct PlayerDrownEvent;
 pub struct AnimalFallEvent(pub Entity);
 
+/// How the player moves; anything but walking uses up stamina
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gait {
+    Walk,
+    Tiptoe,
+    Sprint,
+}
+
+impl Gait {
+    pub fn speed(&self) -> f32 {
+        match self {
+            Gait::Walk => 1.,
+            Gait::Tiptoe => 0.5,
+            Gait::Sprint => 1.6,
+        }
+    }
+
+    /// Factor on the mass the ice has to carry and on how hard cracks get stamped
+    pub fn load(&self) -> f32 {
+        match self {
+            Gait::Walk => 1.,
+            Gait::Tiptoe => 0.5,
+            Gait::Sprint => 1.5,
+        }
+    }
+
+    /// Stamina used per second; walking recovers it
+    fn exertion(&self) -> f32 {
+        match self {
+            Gait::Walk => -0.15,
+            Gait::Tiptoe => 0.2,
+            Gait::Sprint => 0.35,
+        }
+    }
+}
+
+/// Time since the last jump; the player can jump again once it finished
+pub struct JumpCooldown(pub Timer);
+
+impl Default for JumpCooldown {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(1.5, false);
+        timer.set_elapsed(timer.duration());
+        JumpCooldown(timer)
+    }
+}
+
+/// From 0 to 1; once used up, the player has to walk until a third of it came back
+pub struct Stamina {
+    pub value: f32,
+    pub exhausted: bool,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Stamina {
+            value: 1.,
+            exhausted: false,
+        }
+    }
+}
+
 fn spawn_player(
     mut commands: Commands,
     textures: Res<TextureAssets>,
     mut spawn_points: ResMut<SpawnPoints>,
+    level: Res<CurrentLevel>,
+    simulation: Res<IceSimulation>,
+    mut rng: ResMut<GameRng>,
 ) {
-    let random_spawn_point = get_random_spawn_point(&mut spawn_points);
-    let random_direction = get_random_direction();
-    let mut transform = Transform::from_translation(Vec3::new(
-        random_spawn_point.x,
-        random_spawn_point.y,
-        PLAYER_Z,
-    ));
-    transform.rotation = Quat::from_rotation_z(-random_direction.angle_between(Vec2::new(0., 1.)));
+    let random_spawn_point = match level.0.player_spawn {
+        Some(spawn) => {
+            spawn_points.0.push(spawn.into());
+            spawn.into()
+        }
+        // the player has to go somewhere, even on a level without free ice
+        None => get_random_spawn_point(&mut spawn_points, &level.0, &simulation, &mut rng.0)
+            .unwrap_or(Vec2::ZERO),
+    };
+    let random_direction = get_random_direction(&mut rng.0);
+    commands.insert_resource(Stamina::default());
+    commands.insert_resource(JumpCooldown::default());
+    spawn_player_at(
+        &mut commands,
+        &textures,
+        random_spawn_point,
+        random_direction,
+    );
+}
+
+pub fn spawn_player_at(
+    commands: &mut Commands,
+    textures: &TextureAssets,
+    position: Vec2,
+    direction: Vec2,
+) -> Entity {
+    let mut transform = Transform::from_translation(position.extend(PLAYER_Z));
+    transform.rotation = Quat::from_rotation_z(-direction.angle_between(Vec2::new(0., 1.)));
     commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: textures.player.clone(),
@@ -52,8 +158,12 @@ fn spawn_player(
         })
         .insert(Player)
         .insert(Level)
-        .insert(Walking(random_direction))
-        .insert(AnimationTimer(Timer::from_seconds(0.2, true)));
+        .insert(Walking(direction))
+        .insert(Mass(1.))
+        .insert(Footprint { radius: 12. })
+        .insert(Gait::Walk)
+        .insert(AnimationTimer(Timer::from_seconds(0.2, true)))
+        .id()
 }
 
 #[derive(Component)]
@@ -65,13 +175,121 @@ impl Default for Drowning {
     }
 }
 
+fn set_gait(
+    time: Res<Time>,
+    actions: Res<Actions>,
+    mut stamina: ResMut<Stamina>,
+    mut player_query: Query<
+        &mut Gait,
+        (
+            With<Player>,
+            Without<Drowning>,
+            Without<Struggling>,
+            Without<Adrift>,
+            Without<Safe>,
+        ),
+    >,
+) {
+    for mut gait in player_query.iter_mut() {
+        let wanted = if actions.sprint {
+            Gait::Sprint
+        } else if actions.tiptoe {
+            Gait::Tiptoe
+        } else {
+            Gait::Walk
+        };
+        *gait = if stamina.exhausted {
+            Gait::Walk
+        } else {
+            wanted
+        };
+        stamina.value = (stamina.value - gait.exertion() * time.delta_seconds()).clamp(0., 1.);
+        if stamina.value <= 0. {
+            stamina.exhausted = true;
+        } else if stamina.value >= 1. / 3. {
+            stamina.exhausted = false;
+        }
+    }
+}
+
+fn jump(
+    mut commands: Commands,
+    time: Res<Time>,
+    actions: Res<Actions>,
+    textures: Res<TextureAssets>,
+    mut cooldown: ResMut<JumpCooldown>,
+    player_query: Query<
+        (Entity, &Transform),
+        (
+            With<Player>,
+            Without<Airborne>,
+            Without<Drowning>,
+            Without<Struggling>,
+            Without<Adrift>,
+            Without<Safe>,
+        ),
+    >,
+) {
+    cooldown.0.tick(time.delta());
+    if !actions.jump || !cooldown.0.finished() {
+        return;
+    }
+    for (entity, transform) in player_query.iter() {
+        take_off(&mut commands, &textures, entity, transform, 0.);
+        cooldown.0.reset();
+    }
+}
+
+/// Lift `entity` into a jump that is `elapsed` seconds under way, casting a shadow on the ice
+pub fn take_off(
+    commands: &mut Commands,
+    textures: &TextureAssets,
+    entity: Entity,
+    transform: &Transform,
+    elapsed: f32,
+) {
+    let mut shadow_transform = *transform;
+    shadow_transform.translation.z -= 0.1;
+    shadow_transform.scale = Vec3::splat(1.);
+    let shadow = commands
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: textures.player.clone(),
+            sprite: TextureAtlasSprite {
+                color: Color::rgba(0., 0., 0., 0.3),
+                ..Default::default()
+            },
+            transform: shadow_transform,
+            ..Default::default()
+        })
+        .insert(Level)
+        .id();
+    let mut timer = Timer::from_seconds(JUMP_SECONDS, false);
+    timer.set_elapsed(Duration::from_secs_f32(elapsed));
+    commands.entity(entity).insert(Airborne { timer, shadow });
+}
+
 fn move_player(
     time: Res<Time>,
     actions: Res<Actions>,
-    mut player_query: Query<(&mut Transform, &mut Walking), (With<Player>, Without<Drowning>)>,
+    arena: Res<Arena>,
+    simulation: Res<IceSimulation>,
+    mut player_query: Query<
+        (&mut Transform, &mut Walking, &Gait),
+        (
+            With<Player>,
+            Without<Drowning>,
+            Without<Struggling>,
+            Without<Adrift>,
+            Without<Safe>,
+        ),
+    >,
 ) {
-    let speed = 70.;
-    for (mut player_transform, mut walking) in player_query.iter_mut() {
+    for (mut player_transform, mut walking, gait) in player_query.iter_mut() {
+        let speed = 70.
+            * gait.speed()
+            * simulation
+                .terrain(arena.get_current_grid(&player_transform.translation))
+                .speed();
         if let Some(steering) = actions.steering {
             let rotation = Mat2::from_angle(-steering * time.delta_seconds());
             walking.0 = rotation.mul_vec2(walking.0);
@@ -81,16 +299,19 @@ fn move_player(
             walking.0.y * speed * time.delta_seconds(),
             0.,
         );
-        player_transform.translation += movement;
-        player_transform.translation = player_transform.translation.clamp(
-            Vec3::new(
-                -WINDOW_WIDTH / 2. + 16.,
-                -WINDOW_HEIGHT / 2. + 16.,
-                PLAYER_Z,
-            ),
-            Vec3::new(WINDOW_WIDTH / 2. - 16., WINDOW_HEIGHT / 2. - 16., PLAYER_Z),
-        );
+        let target = arena.clamp(player_transform.translation + movement, 16.);
+        // rocks block the way
+        if !simulation.is_rock(arena.get_current_grid(&target)) {
+            player_transform.translation = target;
+        }
         player_transform.rotation =
             Quat::from_rotation_z(-walking.0.angle_between(Vec2::new(0., 1.)));
     }
 }
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum PlayerLabels {
+    SpawnPlayer,
+    SetGait,
+    MovePlayer,
+}
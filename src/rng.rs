@@ -0,0 +1,81 @@
+use crate::GameState;
+use bevy::prelude::*;
+use rand::{random, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+pub struct RngPlugin;
+
+/// This plugin owns the random number generator used for every random decision in a run
+/// The generator is reseeded from [GameSeed] whenever a run starts, so the same seed always leads to
+/// the same starting ice: its thickness and the cracks painted on it. Later draws depend on the frame
+/// times and the player's input, so runs only replay up to the first frame of play.
+/// Unless the player picked a seed, every restart draws a new one.
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let explicit_seed = seed_from_args();
+        let seed = explicit_seed.unwrap_or_else(random);
+        app.insert_resource(GameSeed {
+            value: seed,
+            explicit: explicit_seed.is_some(),
+        })
+        .insert_resource(GameRng::new(seed))
+        .add_system_set(SystemSet::on_enter(GameState::Restart).with_system(draw_seed))
+        .add_system_set(
+            SystemSet::on_enter(GameState::Countdown).with_system(
+                reseed
+                    .exclusive_system()
+                    .at_start()
+                    .label(RngLabels::Reseed),
+            ),
+        );
+    }
+}
+
+/// The seed of the current or next run
+/// Can be passed on the command line with `--seed <number>` or typed in the menu
+pub struct GameSeed {
+    pub value: u64,
+    /// The player chose this seed, so every run replays it
+    pub explicit: bool,
+}
+
+pub struct GameRng(pub ChaCha8Rng);
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+fn reseed(world: &mut World) {
+    let seed = world
+        .get_resource::<GameSeed>()
+        .expect("Failed to get the game seed")
+        .value;
+    world.insert_resource(GameRng::new(seed));
+}
+
+fn draw_seed(mut seed: ResMut<GameSeed>) {
+    if !seed.explicit {
+        seed.value = random();
+    }
+}
+
+fn seed_from_args() -> Option<u64> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(seed) = arg.strip_prefix("--seed=") {
+            return seed.parse().ok();
+        }
+        if arg == "--seed" {
+            return args.next().and_then(|seed| seed.parse().ok());
+        }
+    }
+
+    None
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum RngLabels {
+    Reseed,
+}
@@ -0,0 +1,577 @@
+use crate::animal::{spawn_animal, Animal, AnimalWaves, Species, Steering, Walking};
+use crate::animate::{Airborne, AnimationTimer, Falling};
+use crate::climb::{spawn_struggle_bar, ClimbOuts, Struggling};
+use crate::floe::{spawn_floe, Adrift, Floe};
+use crate::ice::{paint_water_slot, CrackTheIceTimer, FadeCracksTimer, RefreezeTimer, WaterLayer};
+use crate::level::{all_levels, Arena, CurrentLevel, LevelData};
+use crate::loading::{CracksLayer, FontAssets, LevelAssets, TextureAssets};
+use crate::player::{spawn_player_at, take_off, Drowning, JumpCooldown, Player, Stamina};
+use crate::rng::{GameRng, GameSeed};
+use crate::shore::{Rescues, Safe};
+use crate::simulation::{IceSimulation, Slot};
+use crate::ui::GameStopWatch;
+use crate::GameState;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub struct SavePlugin;
+
+/// This plugin writes the running game to disk when the window gets closed
+/// The menu offers to continue a saved run, which is restored right after the countdown started.
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SavedRun(read_snapshot()))
+            .insert_resource(PendingSnapshot(None))
+            .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(check_saved_run))
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(save_on_close))
+            .add_system_set(
+                SystemSet::on_update(GameState::Countdown).with_system(restore_snapshot),
+            );
+    }
+}
+
+/// The run found on disk, offered as "Continue" in the menu
+pub struct SavedRun(pub Option<Snapshot>);
+
+/// The run to restore in the next countdown
+pub struct PendingSnapshot(pub Option<Snapshot>);
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub seed: u64,
+    /// Name of the level the run was played on
+    pub level: String,
+    /// Position in the stream of the random number generator as high and low half
+    rng_word_pos: (u64, u64),
+    simulation: IceSimulation,
+    /// Painted pixels of every cracks tile as pixel index and color
+    cracks: Vec<Vec<(u32, [u8; 4])>>,
+    player: WalkerSnapshot,
+    /// Seconds into the jump the player was in
+    jump: Option<f32>,
+    /// Seconds and progress of the player's struggle to climb out of the water
+    struggle: Option<(f32, f32)>,
+    animals: Vec<AnimalSnapshot>,
+    floes: Vec<FloeSnapshot>,
+    score: f32,
+    rescues: usize,
+    stamina: f32,
+    climb_outs: usize,
+    crack_timer: f32,
+    fade_timer: f32,
+    refreeze_timer: f32,
+    /// Seconds since the last jump
+    jump_timer: f32,
+    wave: usize,
+    wave_timer: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WalkerSnapshot {
+    translation: [f32; 3],
+    scale: [f32; 3],
+    direction: [f32; 2],
+    drowning: Option<f32>,
+    /// Index of the carrying floe and the offset to its center
+    adrift: Option<(usize, [f32; 2])>,
+}
+
+impl WalkerSnapshot {
+    fn new(
+        transform: &Transform,
+        walking: &Walking,
+        drowning: Option<&Drowning>,
+        adrift: Option<&Adrift>,
+        floes: &[Entity],
+    ) -> Self {
+        WalkerSnapshot {
+            translation: transform.translation.to_array(),
+            scale: transform.scale.to_array(),
+            direction: walking.0.to_array(),
+            drowning: drowning.map(|drowning| drowning.0.elapsed_secs()),
+            adrift: adrift.and_then(|adrift| {
+                floes
+                    .iter()
+                    .position(|floe| *floe == adrift.floe)
+                    .map(|index| (index, adrift.offset.to_array()))
+            }),
+        }
+    }
+
+    fn transform(&self) -> Transform {
+        let direction = Vec2::from(self.direction);
+        Transform {
+            translation: Vec3::from(self.translation),
+            rotation: Quat::from_rotation_z(-direction.angle_between(Vec2::new(0., 1.))),
+            scale: Vec3::from(self.scale),
+        }
+    }
+
+    fn restore(&self, commands: &mut Commands, entity: Entity, floes: &[Entity]) {
+        let mut walker = commands.entity(entity);
+        walker.insert(self.transform());
+        if let Some(elapsed) = self.drowning {
+            let mut drowning = Drowning::default();
+            drowning.0.set_elapsed(Duration::from_secs_f32(elapsed));
+            walker.insert(drowning);
+        }
+        if let Some((index, offset)) = self.adrift {
+            walker.insert(Adrift {
+                floe: floes[index],
+                offset: Vec2::from(offset),
+            });
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnimalSnapshot {
+    walker: WalkerSnapshot,
+    species: Species,
+    steering: Option<f32>,
+    /// Seconds since the animal started falling out of the sky
+    falling: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FloeSnapshot {
+    slots: Vec<Slot>,
+    translation: [f32; 3],
+    scale: [f32; 3],
+    velocity: [f32; 2],
+    spin: f32,
+    angle: f32,
+    sinking: f32,
+}
+
+impl Snapshot {
+    /// Whether the run was played on `level` as it is now
+    fn fits(&self, level: &LevelData) -> bool {
+        self.level == level.name && self.fits_arena(&level.arena)
+    }
+
+    fn fits_arena(&self, arena: &Arena) -> bool {
+        self.simulation.width() == arena.grid_x && self.simulation.height() == arena.grid_y
+    }
+}
+
+/// The save file in the user's data directory
+#[cfg(not(target_arch = "wasm32"))]
+fn save_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("on_thin_ice").join("save.ron"))
+}
+
+/// The browser has no file system to save to
+#[cfg(target_arch = "wasm32")]
+fn save_path() -> Option<PathBuf> {
+    None
+}
+
+fn read_snapshot() -> Option<Snapshot> {
+    let bytes = std::fs::read(save_path()?).ok()?;
+    match ron::de::from_bytes(&bytes) {
+        Ok(snapshot) => Some(snapshot),
+        Err(error) => {
+            warn!("Failed to read the saved run, deleting it: {}", error);
+            delete_snapshot();
+            None
+        }
+    }
+}
+
+/// The saved run is gone once it is continued
+pub fn delete_snapshot() {
+    let path = match save_path() {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(error) = std::fs::remove_file(path) {
+        warn!("Failed to delete the saved run: {}", error);
+    }
+}
+
+fn write_snapshot(path: &Path, text: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, text)
+}
+
+/// Drop a saved run whose level is gone or changed size since it was saved
+fn check_saved_run(
+    mut saved_run: ResMut<SavedRun>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<LevelData>>,
+) {
+    let snapshot = match saved_run.0.as_ref() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+    if !all_levels(&level_assets, &levels)
+        .iter()
+        .any(|level| snapshot.fits(level))
+    {
+        warn!(
+            "The saved run on level '{}' does not fit any level, deleting it",
+            snapshot.level
+        );
+        saved_run.0 = None;
+        delete_snapshot();
+    }
+}
+
+/// The resources a saved run keeps besides the painted layers
+#[derive(SystemParam)]
+struct RunState<'w, 's> {
+    seed: Res<'w, GameSeed>,
+    level: Res<'w, CurrentLevel>,
+    rng: Res<'w, GameRng>,
+    simulation: Res<'w, IceSimulation>,
+    stop_watch: Res<'w, GameStopWatch>,
+    crack_timer: Res<'w, CrackTheIceTimer>,
+    fade_timer: Res<'w, FadeCracksTimer>,
+    refreeze_timer: Res<'w, RefreezeTimer>,
+    jump_cooldown: Res<'w, JumpCooldown>,
+    waves: Res<'w, AnimalWaves>,
+    rescues: Res<'w, Rescues>,
+    stamina: Res<'w, Stamina>,
+    climb_outs: Res<'w, ClimbOuts>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// The resources a restored run overwrites
+#[derive(SystemParam)]
+struct RunStateMut<'w, 's> {
+    rng: ResMut<'w, GameRng>,
+    simulation: ResMut<'w, IceSimulation>,
+    stop_watch: ResMut<'w, GameStopWatch>,
+    crack_timer: ResMut<'w, CrackTheIceTimer>,
+    fade_timer: ResMut<'w, FadeCracksTimer>,
+    refreeze_timer: ResMut<'w, RefreezeTimer>,
+    jump_cooldown: ResMut<'w, JumpCooldown>,
+    waves: ResMut<'w, AnimalWaves>,
+    rescues: ResMut<'w, Rescues>,
+    stamina: ResMut<'w, Stamina>,
+    climb_outs: ResMut<'w, ClimbOuts>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+/// The walkers and floes a saved run keeps
+#[derive(SystemParam)]
+struct SavedEntities<'w, 's> {
+    player: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Walking,
+            Option<&'static Adrift>,
+            Option<&'static Airborne>,
+            Option<&'static Struggling>,
+        ),
+        (With<Player>, Without<Drowning>, Without<Safe>),
+    >,
+    animals: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Walking,
+            &'static Species,
+            &'static Steering,
+            Option<&'static Drowning>,
+            Option<&'static Adrift>,
+            Option<&'static AnimationTimer>,
+            Option<&'static Falling>,
+        ),
+        With<Animal>,
+    >,
+    floes: Query<'w, 's, (Entity, &'static Floe, &'static Transform)>,
+}
+
+fn save_on_close(
+    mut close_events: EventReader<WindowCloseRequested>,
+    cracks_layer: Res<CracksLayer>,
+    images: Res<Assets<Image>>,
+    run: RunState,
+    entities: SavedEntities,
+) {
+    if close_events.iter().next().is_none() {
+        return;
+    }
+    let path = match save_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let RunState {
+        seed,
+        level,
+        rng,
+        simulation,
+        stop_watch,
+        crack_timer,
+        fade_timer,
+        refreeze_timer,
+        jump_cooldown,
+        waves,
+        rescues,
+        stamina,
+        climb_outs,
+        ..
+    } = run;
+    let SavedEntities {
+        player,
+        animals,
+        floes,
+    } = entities;
+    // a drowning player already lost the run and a safe one already banked it
+    let (transform, walking, adrift, airborne, struggling) = match player.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let floe_entities: Vec<Entity> = floes.iter().map(|(entity, _, _)| entity).collect();
+    let word_pos = rng.0.get_word_pos();
+
+    let snapshot = Snapshot {
+        seed: seed.value,
+        level: level.0.name.clone(),
+        rng_word_pos: ((word_pos >> 64) as u64, word_pos as u64),
+        simulation: simulation.clone(),
+        cracks: cracks_layer
+            .tiles()
+            .map(|(tile, _)| {
+                let image = images
+                    .get(tile.clone())
+                    .expect("Failed to find a cracks tile");
+                image
+                    .data
+                    .chunks(4)
+                    .enumerate()
+                    .filter(|(_, pixel)| pixel.iter().any(|value| *value > 0))
+                    .map(|(index, pixel)| (index as u32, [pixel[0], pixel[1], pixel[2], pixel[3]]))
+                    .collect()
+            })
+            .collect(),
+        player: WalkerSnapshot::new(transform, walking, None, adrift, &floe_entities),
+        jump: airborne.map(|airborne| airborne.timer.elapsed_secs()),
+        struggle: struggling
+            .map(|struggling| (struggling.timer.elapsed_secs(), struggling.progress)),
+        animals: animals
+            .iter()
+            .map(
+                |(transform, walking, species, steering, drowning, adrift, timer, falling)| {
+                    AnimalSnapshot {
+                        walker: WalkerSnapshot::new(
+                            transform,
+                            walking,
+                            drowning,
+                            adrift,
+                            &floe_entities,
+                        ),
+                        species: *species,
+                        steering: steering.0,
+                        falling: falling.and(timer).map(|timer| timer.0.elapsed_secs()),
+                    }
+                },
+            )
+            .collect(),
+        floes: floes
+            .iter()
+            .map(|(_, floe, transform)| FloeSnapshot {
+                slots: floe.slots.clone(),
+                translation: transform.translation.to_array(),
+                scale: transform.scale.to_array(),
+                velocity: floe.velocity.to_array(),
+                spin: floe.spin,
+                angle: floe.angle,
+                sinking: floe.sinking.elapsed_secs(),
+            })
+            .collect(),
+        score: stop_watch.0.elapsed_secs(),
+        rescues: rescues.0,
+        stamina: stamina.value,
+        climb_outs: climb_outs.0,
+        crack_timer: crack_timer.0.elapsed_secs(),
+        fade_timer: fade_timer.0.elapsed_secs(),
+        refreeze_timer: refreeze_timer.0.elapsed_secs(),
+        jump_timer: jump_cooldown.0.elapsed_secs(),
+        wave: waves.current(),
+        wave_timer: waves.elapsed_secs(),
+    };
+
+    let result = ron::ser::to_string(&snapshot)
+        .map_err(|error| error.to_string())
+        .and_then(|text| write_snapshot(&path, &text).map_err(|error| error.to_string()));
+    match result {
+        Ok(()) => info!("Saved the run to {}", path.display()),
+        Err(error) => warn!("Failed to save the run: {}", error),
+    }
+}
+
+fn restore_snapshot(
+    mut commands: Commands,
+    mut pending: ResMut<PendingSnapshot>,
+    assets: (Res<TextureAssets>, Res<FontAssets>),
+    mut images: ResMut<Assets<Image>>,
+    layers: (Res<Arena>, Res<CracksLayer>, Res<WaterLayer>),
+    run: RunStateMut,
+    walkers: Query<Entity, Or<(With<Player>, With<Animal>)>>,
+) {
+    let snapshot = match pending.0.take() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+    let (arena, cracks_layer, water_layer) = layers;
+    if !snapshot.fits_arena(&arena) {
+        warn!("The saved run does not fit the arena, starting a new run instead");
+        return;
+    }
+    let (textures, font_assets) = assets;
+    let RunStateMut {
+        mut rng,
+        mut simulation,
+        mut stop_watch,
+        mut crack_timer,
+        mut fade_timer,
+        mut refreeze_timer,
+        mut jump_cooldown,
+        mut waves,
+        mut rescues,
+        mut stamina,
+        mut climb_outs,
+        ..
+    } = run;
+
+    let (high, low) = snapshot.rng_word_pos;
+    rng.0.set_word_pos(((high as u128) << 64) | low as u128);
+    *simulation = snapshot.simulation;
+    stop_watch
+        .0
+        .set_elapsed(Duration::from_secs_f32(snapshot.score));
+    crack_timer
+        .0
+        .set_elapsed(Duration::from_secs_f32(snapshot.crack_timer));
+    fade_timer
+        .0
+        .set_elapsed(Duration::from_secs_f32(snapshot.fade_timer));
+    refreeze_timer
+        .0
+        .set_elapsed(Duration::from_secs_f32(snapshot.refreeze_timer));
+    jump_cooldown
+        .0
+        .set_elapsed(Duration::from_secs_f32(snapshot.jump_timer));
+    waves.restore(snapshot.wave, snapshot.wave_timer);
+    rescues.0 = snapshot.rescues;
+    climb_outs.0 = snapshot.climb_outs;
+    *stamina = Stamina {
+        value: snapshot.stamina,
+        exhausted: snapshot.stamina <= 0.,
+    };
+
+    for ((tile, _), pixels) in cracks_layer.tiles().zip(snapshot.cracks.iter()) {
+        let image = images
+            .get_mut(tile.clone())
+            .expect("Failed to find a cracks tile");
+        image.data.iter_mut().for_each(|value| *value = 0);
+        for (index, pixel) in pixels {
+            let index = *index as usize * 4;
+            image.data[index..index + 4].copy_from_slice(pixel);
+        }
+    }
+    let water = images
+        .get_mut(water_layer.layer.clone())
+        .expect("Failed to find the water layer texture");
+    for y in 0..simulation.height() {
+        for x in 0..simulation.width() {
+            paint_water_slot(water, &simulation, &arena, (x, y));
+        }
+    }
+
+    let floes: Vec<Entity> = snapshot
+        .floes
+        .iter()
+        .map(|floe| {
+            let entity = spawn_floe(
+                &mut commands,
+                &mut images,
+                &textures,
+                &cracks_layer,
+                &arena,
+                &floe.slots,
+            );
+            let mut sinking = Timer::from_seconds(4., false);
+            sinking.set_elapsed(Duration::from_secs_f32(floe.sinking));
+            commands
+                .entity(entity)
+                .insert(Transform {
+                    translation: Vec3::from(floe.translation),
+                    rotation: Quat::from_rotation_z(floe.angle),
+                    scale: Vec3::from(floe.scale),
+                })
+                .insert(Floe {
+                    slots: floe.slots.clone(),
+                    velocity: Vec2::from(floe.velocity),
+                    spin: floe.spin,
+                    angle: floe.angle,
+                    sinking,
+                });
+            entity
+        })
+        .collect();
+
+    for entity in walkers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let player = &snapshot.player;
+    let entity = spawn_player_at(
+        &mut commands,
+        &textures,
+        Vec3::from(player.translation).truncate(),
+        Vec2::from(player.direction),
+    );
+    player.restore(&mut commands, entity, &floes);
+    if let Some(elapsed) = snapshot.jump {
+        take_off(
+            &mut commands,
+            &textures,
+            entity,
+            &player.transform(),
+            elapsed,
+        );
+    }
+    if let Some((elapsed, progress)) = snapshot.struggle {
+        let mut struggling = Struggling::new(snapshot.climb_outs);
+        struggling
+            .timer
+            .set_elapsed(Duration::from_secs_f32(elapsed));
+        struggling.progress = progress;
+        commands.entity(entity).insert(struggling);
+        spawn_struggle_bar(&mut commands, &font_assets);
+    }
+    for animal in snapshot.animals.iter() {
+        let entity = spawn_animal(
+            &mut commands,
+            &textures,
+            animal.species,
+            Vec3::from(animal.walker.translation).truncate(),
+            Vec2::from(animal.walker.direction),
+        );
+        animal.walker.restore(&mut commands, entity, &floes);
+        commands.entity(entity).insert(Steering(animal.steering));
+        if let Some(elapsed) = animal.falling {
+            let mut timer = Timer::from_seconds(2., false);
+            timer.set_elapsed(Duration::from_secs_f32(elapsed));
+            commands
+                .entity(entity)
+                .insert(Falling)
+                .insert(AnimationTimer(timer));
+        }
+    }
+}
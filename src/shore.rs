@@ -0,0 +1,175 @@
+use crate::animal::Animal;
+use crate::animate::Falling;
+use crate::climb::Struggling;
+use crate::floe::Adrift;
+use crate::ice::IceLabels;
+use crate::level::{Arena, CurrentLevel, Goal};
+use crate::loading::FontAssets;
+use crate::player::{Drowning, Player};
+use crate::simulation::IceSimulation;
+use crate::{GameState, Level};
+use bevy::prelude::*;
+
+pub struct ShorePlugin;
+
+/// This plugin ends runs on [Goal::ReachShore] levels once the player walks ashore
+/// Animals reaching the shore before that leave the arena and count as rescued.
+impl Plugin for ShorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerSafeEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Countdown).with_system(spawn_rescue_counter),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        rescue_animals
+                            .label(ShoreLabels::ReachShore)
+                            .after(IceLabels::CheckIceGrid),
+                    )
+                    .with_system(
+                        reach_shore
+                            .label(ShoreLabels::ReachShore)
+                            .after(IceLabels::CheckIceGrid),
+                    )
+                    .with_system(update_rescue_counter.after(ShoreLabels::ReachShore)),
+            );
+    }
+}
+
+/// Animals that made it ashore during the current run
+pub struct Rescues(pub usize);
+
+/// The player is ashore and the run is over
+#[derive(Component)]
+pub struct Safe;
+
+pub struct PlayerSafeEvent;
+
+#[derive(Component)]
+struct RescueText;
+
+fn spawn_rescue_counter(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    level: Res<CurrentLevel>,
+) {
+    commands.insert_resource(Rescues(0));
+    if level.0.goal != Goal::ReachShore {
+        return;
+    }
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.0), Val::Px(40.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(70.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: UiColor(Color::Rgba {
+                red: 0.7,
+                green: 0.7,
+                blue: 0.7,
+                alpha: 0.7,
+            }),
+            ..Default::default()
+        })
+        .insert(Level)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: rescue_text(0),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 30.0,
+                                color: Color::rgb_u8(34, 32, 52),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(RescueText);
+        });
+}
+
+fn rescue_text(rescues: usize) -> String {
+    format!("Rescued: {}", rescues)
+}
+
+fn rescue_animals(
+    mut commands: Commands,
+    level: Res<CurrentLevel>,
+    arena: Res<Arena>,
+    simulation: Res<IceSimulation>,
+    mut rescues: ResMut<Rescues>,
+    animals: Query<
+        (Entity, &Transform),
+        (
+            With<Animal>,
+            Without<Drowning>,
+            Without<Falling>,
+            Without<Adrift>,
+        ),
+    >,
+) {
+    if level.0.goal != Goal::ReachShore {
+        return;
+    }
+    for (entity, transform) in animals.iter() {
+        if simulation.is_shore(arena.get_current_grid(&transform.translation)) {
+            commands.entity(entity).despawn_recursive();
+            rescues.0 += 1;
+        }
+    }
+}
+
+fn reach_shore(
+    mut commands: Commands,
+    level: Res<CurrentLevel>,
+    arena: Res<Arena>,
+    simulation: Res<IceSimulation>,
+    mut player_safe_events: EventWriter<PlayerSafeEvent>,
+    player: Query<
+        (Entity, &Transform),
+        (
+            With<Player>,
+            Without<Drowning>,
+            Without<Struggling>,
+            Without<Adrift>,
+            Without<Safe>,
+        ),
+    >,
+) {
+    if level.0.goal != Goal::ReachShore {
+        return;
+    }
+    for (entity, transform) in player.iter() {
+        if simulation.is_shore(arena.get_current_grid(&transform.translation)) {
+            commands.entity(entity).insert(Safe);
+            player_safe_events.send(PlayerSafeEvent);
+        }
+    }
+}
+
+fn update_rescue_counter(rescues: Res<Rescues>, mut text: Query<&mut Text, With<RescueText>>) {
+    if !rescues.is_changed() {
+        return;
+    }
+    for mut text in text.iter_mut() {
+        text.sections[0].value = rescue_text(rescues.0);
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum ShoreLabels {
+    ReachShore,
+}
@@ -0,0 +1,791 @@
+//! Engine independent model of the ice
+//! It knows nothing about Bevy; the systems in `ice.rs` translate entities to grid slots,
+//! call [IceSimulation::step] and act on the returned events.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub type Slot = (usize, usize);
+
+/// Something standing on the ice
+/// A mass of 1 is a normal walker; heavier occupants crack and break slots faster.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Occupant {
+    pub slot: Slot,
+    pub mass: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SlotState {
+    /// Intact ice; `stress` sums up load times seconds it carried so far
+    Ice {
+        stress: f64,
+    },
+    Cracks {
+        since: f64,
+        last_visited: f64,
+    },
+    Brocken {
+        since: f64,
+    },
+    /// Open water that never freezes over
+    OpenWater,
+    /// Never breaks and can't be walked over
+    Rock,
+    /// Solid ground that never breaks
+    Shore,
+}
+
+impl SlotState {
+    pub fn is_water(&self) -> bool {
+        matches!(self, SlotState::Brocken { .. } | SlotState::OpenWater)
+    }
+
+    /// Cracks that never heal and break on the next visit
+    pub const PERMANENT_CRACKS: SlotState = SlotState::Cracks {
+        since: f64::NEG_INFINITY,
+        last_visited: f64::INFINITY,
+    };
+}
+
+/// What covers a slot, independent of the state of the ice below
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Terrain {
+    Ice,
+    /// Slows walkers down and hides cracks
+    Snow,
+    /// Breaks as soon as anything steps on it
+    Slush,
+}
+
+impl Terrain {
+    /// Factor on the speed of walkers crossing the slot
+    pub fn speed(&self) -> f32 {
+        match self {
+            Terrain::Ice => 1.,
+            Terrain::Snow => 0.6,
+            Terrain::Slush => 0.8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IceEvent {
+    Cracked(Slot),
+    /// A crack spread into this slot from its neighbours
+    Spread(Slot),
+    Broke(Slot),
+    Healed(Slot),
+    Refrozen(Slot),
+    /// These intact slots got cut off from the rest of the ice and are now open water
+    Detached(Vec<Slot>),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IceConfig {
+    /// Seconds a slot of thickness 2 has to carry a load of 1 before it cracks
+    /// Thickness 1 and below cracks on the first step.
+    pub crack_delay: f64,
+    /// Seconds a cracked slot of thickness 1 holds before the next visit breaks it
+    pub break_delay: f64,
+    /// Seconds a cracked slot has to stay unvisited to heal back to ice
+    pub heal_delay: f64,
+    /// Seconds until a broken slot freezes over again
+    pub refreeze_delay: f64,
+    /// Seconds between two rounds of crack propagation
+    pub spread_interval: f64,
+    /// Chance per round and damaged neighbour that a crack spreads into a slot of thickness 1
+    pub spread_chance: f64,
+}
+
+impl Default for IceConfig {
+    fn default() -> Self {
+        IceConfig {
+            crack_delay: 0.3,
+            break_delay: 0.4,
+            heal_delay: 10.,
+            refreeze_delay: 30.,
+            spread_interval: 0.5,
+            spread_chance: 0.01,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IceSimulation {
+    width: usize,
+    height: usize,
+    elapsed: f64,
+    since_spread: f64,
+    slots: Vec<Vec<SlotState>>,
+    thickness: Vec<Vec<f32>>,
+    terrain: Vec<Vec<Terrain>>,
+    water_regions: Vec<Vec<Slot>>,
+    water_region_of: Vec<Vec<Option<usize>>>,
+    water_changed: bool,
+    pub config: IceConfig,
+}
+
+impl IceSimulation {
+    pub fn new(width: usize, height: usize) -> Self {
+        IceSimulation {
+            width,
+            height,
+            elapsed: 0.,
+            since_spread: 0.,
+            slots: vec![vec![SlotState::Ice { stress: 0. }; width]; height],
+            thickness: vec![vec![1.; width]; height],
+            terrain: vec![vec![Terrain::Ice; width]; height],
+            water_regions: vec![],
+            water_region_of: vec![vec![None; width]; height],
+            water_changed: false,
+            config: IceConfig::default(),
+        }
+    }
+
+    /// Crack every slot closer than `depth` slots to the edge of the grid
+    /// These cracks never heal.
+    pub fn crack_edges(&mut self, depth: usize) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if x < depth
+                    || y < depth
+                    || x >= self.width.saturating_sub(depth)
+                    || y >= self.height.saturating_sub(depth)
+                {
+                    self.slots[y][x] = SlotState::PERMANENT_CRACKS;
+                }
+            }
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn contains(&self, (x, y): Slot) -> bool {
+        x < self.width && y < self.height
+    }
+
+    pub fn state(&self, (x, y): Slot) -> SlotState {
+        self.slots[y][x]
+    }
+
+    /// Overwrite the state of a slot, e.g. to set up a level
+    pub fn set_state(&mut self, (x, y): Slot, state: SlotState) {
+        self.water_changed |= self.slots[y][x].is_water() || state.is_water();
+        self.slots[y][x] = state;
+    }
+
+    pub fn thickness(&self, (x, y): Slot) -> f32 {
+        self.thickness[y][x]
+    }
+
+    pub fn set_thickness(&mut self, (x, y): Slot, thickness: f32) {
+        self.thickness[y][x] = thickness.max(0.);
+    }
+
+    pub fn terrain(&self, (x, y): Slot) -> Terrain {
+        self.terrain[y][x]
+    }
+
+    pub fn set_terrain(&mut self, (x, y): Slot, terrain: Terrain) {
+        self.terrain[y][x] = terrain;
+    }
+
+    /// Fill the thickness map with smooth value noise between `min` and `max`
+    /// Random values on a lattice of `scale` slots are interpolated bilinearly.
+    pub fn generate_thickness(&mut self, rng: &mut impl Rng, min: f32, max: f32, scale: usize) {
+        let scale = scale.max(1);
+        let lattice_x = self.width / scale + 2;
+        let lattice_y = self.height / scale + 2;
+        let lattice: Vec<Vec<f32>> = (0..lattice_y)
+            .map(|_| (0..lattice_x).map(|_| rng.gen()).collect())
+            .collect();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (cell_x, cell_y) = (x / scale, y / scale);
+                let fraction_x = (x % scale) as f32 / scale as f32;
+                let fraction_y = (y % scale) as f32 / scale as f32;
+                let bottom = lattice[cell_y][cell_x] * (1. - fraction_x)
+                    + lattice[cell_y][cell_x + 1] * fraction_x;
+                let top = lattice[cell_y + 1][cell_x] * (1. - fraction_x)
+                    + lattice[cell_y + 1][cell_x + 1] * fraction_x;
+                let noise = bottom * (1. - fraction_y) + top * fraction_y;
+                self.thickness[y][x] = min + (max - min) * noise;
+            }
+        }
+    }
+
+    /// How far a slot got on its way back to intact ice, from 0 to 1
+    pub fn recovery(&self, (x, y): Slot) -> f64 {
+        let progress = match self.slots[y][x] {
+            SlotState::Ice { .. } | SlotState::OpenWater | SlotState::Rock | SlotState::Shore => {
+                return 0.
+            }
+            SlotState::Cracks { last_visited, .. } => {
+                (self.elapsed - last_visited) / self.config.heal_delay
+            }
+            SlotState::Brocken { since } => (self.elapsed - since) / self.config.refreeze_delay,
+        };
+
+        progress.clamp(0., 1.)
+    }
+
+    /// Advance the simulation by `dt` seconds
+    /// `occupants` holds every entity currently standing on the ice.
+    /// The masses of occupants sharing a slot add up to the load on that slot.
+    pub fn step(&mut self, dt: f64, occupants: &[Occupant], rng: &mut impl Rng) -> Vec<IceEvent> {
+        self.elapsed += dt;
+        let mut events = vec![];
+
+        let mut loads: BTreeMap<Slot, f64> = BTreeMap::new();
+        for occupant in occupants {
+            if self.contains(occupant.slot) {
+                *loads.entry(occupant.slot).or_default() += occupant.mass.max(0.) as f64;
+            }
+        }
+
+        for (slot, load) in loads {
+            let (x, y) = slot;
+            let intact = matches!(
+                self.slots[y][x],
+                SlotState::Ice { .. } | SlotState::Cracks { .. }
+            );
+            if intact && self.terrain[y][x] == Terrain::Slush {
+                self.slots[y][x] = SlotState::Brocken {
+                    since: self.elapsed,
+                };
+                events.push(IceEvent::Broke(slot));
+                continue;
+            }
+            match self.slots[y][x] {
+                SlotState::Ice { stress } => {
+                    let stress = stress + dt * load;
+                    let crack_delay =
+                        (self.thickness[y][x] as f64 - 1.).max(0.) * self.config.crack_delay;
+                    if stress >= crack_delay {
+                        self.slots[y][x] = SlotState::Cracks {
+                            since: self.elapsed,
+                            last_visited: self.elapsed,
+                        };
+                        events.push(IceEvent::Cracked(slot));
+                    } else {
+                        self.slots[y][x] = SlotState::Ice { stress };
+                    }
+                }
+                SlotState::Cracks {
+                    since,
+                    last_visited,
+                } => {
+                    let break_delay =
+                        self.config.break_delay * self.thickness[y][x] as f64 / load.max(0.01);
+                    if self.elapsed - since > break_delay {
+                        self.slots[y][x] = SlotState::Brocken {
+                            since: self.elapsed,
+                        };
+                        events.push(IceEvent::Broke(slot));
+                    } else {
+                        self.slots[y][x] = SlotState::Cracks {
+                            since,
+                            last_visited: last_visited.max(self.elapsed),
+                        };
+                    }
+                }
+                SlotState::Brocken { .. }
+                | SlotState::OpenWater
+                | SlotState::Rock
+                | SlotState::Shore => (),
+            }
+        }
+        self.since_spread += dt;
+        while self.since_spread >= self.config.spread_interval {
+            self.since_spread -= self.config.spread_interval;
+            self.spread(rng, &mut events);
+        }
+        self.recover(&mut events);
+
+        if events
+            .iter()
+            .any(|event| matches!(event, IceEvent::Broke(_)))
+        {
+            self.detach_floes(&mut events);
+        }
+        self.water_changed |= events.iter().any(|event| {
+            matches!(
+                event,
+                IceEvent::Broke(_) | IceEvent::Refrozen(_) | IceEvent::Detached(_)
+            )
+        });
+        if self.water_changed {
+            self.update_water_regions();
+        }
+
+        events
+    }
+
+    /// Turn intact areas enclosed by water into open water and report them as floes
+    /// Areas touching the edge of the grid, rocks or the shore stay attached. The largest intact area
+    /// never counts as enclosed, even if water cut it off from every edge.
+    fn detach_floes(&mut self, events: &mut Vec<IceEvent>) {
+        let (regions, _) = self
+            .flood_fill(|state| matches!(state, SlotState::Ice { .. } | SlotState::Cracks { .. }));
+        let largest = regions
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, region)| region.len())
+            .map(|(index, _)| index);
+
+        for (index, region) in regions.into_iter().enumerate() {
+            let touches_edge = region
+                .iter()
+                .any(|&(x, y)| x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1);
+            let anchored = region.iter().any(|&slot| {
+                self.neighbours(slot)
+                    .any(|neighbour| self.is_rock(neighbour) || self.is_shore(neighbour))
+            });
+            if Some(index) == largest || touches_edge || anchored {
+                continue;
+            }
+            for &(x, y) in region.iter() {
+                self.slots[y][x] = SlotState::Brocken {
+                    since: self.elapsed,
+                };
+            }
+            events.push(IceEvent::Detached(region));
+        }
+    }
+
+    /// All connected areas of broken slots
+    pub fn water_regions(&self) -> &[Vec<Slot>] {
+        &self.water_regions
+    }
+
+    /// Index into [IceSimulation::water_regions] of the region containing `slot`
+    pub fn water_region(&self, (x, y): Slot) -> Option<usize> {
+        self.water_region_of[y][x]
+    }
+
+    pub fn is_water(&self, (x, y): Slot) -> bool {
+        self.slots[y][x].is_water()
+    }
+
+    pub fn is_rock(&self, (x, y): Slot) -> bool {
+        matches!(self.slots[y][x], SlotState::Rock)
+    }
+
+    /// Share of `slots` that are water, from 0 to 1
+    pub fn water_share(&self, slots: &[Slot]) -> f32 {
+        if slots.is_empty() {
+            return 0.;
+        }
+        let water = slots.iter().filter(|&&slot| self.is_water(slot)).count();
+
+        water as f32 / slots.len() as f32
+    }
+
+    pub fn is_shore(&self, (x, y): Slot) -> bool {
+        matches!(self.slots[y][x], SlotState::Shore)
+    }
+
+    fn update_water_regions(&mut self) {
+        self.water_changed = false;
+        let (regions, region_of) = self.flood_fill(SlotState::is_water);
+        self.water_regions = regions;
+        self.water_region_of = region_of;
+    }
+
+    /// Group all slots matching `include` into regions connected over slot edges
+    fn flood_fill(
+        &self,
+        include: impl Fn(&SlotState) -> bool,
+    ) -> (Vec<Vec<Slot>>, Vec<Vec<Option<usize>>>) {
+        let mut regions = vec![];
+        let mut region_of = vec![vec![None; self.width]; self.height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if region_of[y][x].is_some() || !include(&self.slots[y][x]) {
+                    continue;
+                }
+                let index = regions.len();
+                let mut region = vec![];
+                let mut open = vec![(x, y)];
+                region_of[y][x] = Some(index);
+                while let Some(slot) = open.pop() {
+                    region.push(slot);
+                    for (neighbour_x, neighbour_y) in self.neighbours(slot) {
+                        if region_of[neighbour_y][neighbour_x].is_none()
+                            && include(&self.slots[neighbour_y][neighbour_x])
+                        {
+                            region_of[neighbour_y][neighbour_x] = Some(index);
+                            open.push((neighbour_x, neighbour_y));
+                        }
+                    }
+                }
+                regions.push(region);
+            }
+        }
+
+        (regions, region_of)
+    }
+
+    /// One round of the crack automaton
+    /// Every intact slot may crack with a chance growing with the number of damaged neighbours.
+    /// The round works on the states from before it started, so cracks spread one slot per round.
+    fn spread(&mut self, rng: &mut impl Rng, events: &mut Vec<IceEvent>) {
+        let mut spread = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !matches!(self.slots[y][x], SlotState::Ice { .. }) {
+                    continue;
+                }
+                let damaged_neighbours = self
+                    .neighbours((x, y))
+                    .filter(|&(x, y)| {
+                        matches!(
+                            self.slots[y][x],
+                            SlotState::Cracks { .. } | SlotState::Brocken { .. }
+                        )
+                    })
+                    .count();
+                if damaged_neighbours == 0 {
+                    continue;
+                }
+                let chance = (self.config.spread_chance / self.thickness[y][x].max(0.1) as f64)
+                    .clamp(0., 1.);
+                let intact_chance = (1. - chance).powi(damaged_neighbours as i32);
+                if rng.gen::<f64>() >= intact_chance {
+                    spread.push((x, y));
+                }
+            }
+        }
+
+        for (x, y) in spread {
+            self.slots[y][x] = SlotState::Cracks {
+                since: self.elapsed,
+                last_visited: self.elapsed,
+            };
+            events.push(IceEvent::Spread((x, y)));
+        }
+    }
+
+    /// The up to four slots sharing an edge with `slot`
+    pub fn neighbours(&self, (x, y): Slot) -> impl Iterator<Item = Slot> {
+        let (width, height) = (self.width, self.height);
+        [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .into_iter()
+        .filter(move |&(x, y)| x < width && y < height)
+    }
+
+    fn recover(&mut self, events: &mut Vec<IceEvent>) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let recovered = match self.slots[y][x] {
+                    SlotState::Ice { .. }
+                    | SlotState::OpenWater
+                    | SlotState::Rock
+                    | SlotState::Shore => continue,
+                    SlotState::Cracks { last_visited, .. } => {
+                        if self.elapsed - last_visited < self.config.heal_delay {
+                            continue;
+                        }
+                        IceEvent::Healed((x, y))
+                    }
+                    SlotState::Brocken { since } => {
+                        if self.elapsed - since < self.config.refreeze_delay {
+                            continue;
+                        }
+                        IceEvent::Refrozen((x, y))
+                    }
+                };
+                self.slots[y][x] = SlotState::Ice { stress: 0. };
+                events.push(recovered);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn simulation(width: usize, height: usize) -> IceSimulation {
+        let mut simulation = IceSimulation::new(width, height);
+        simulation.config.spread_chance = 0.;
+        simulation
+    }
+
+    fn rng() -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(42)
+    }
+
+    fn occupant(slot: Slot, mass: f32) -> Occupant {
+        Occupant { slot, mass }
+    }
+
+    #[test]
+    fn thin_ice_cracks_on_the_first_step() {
+        let mut rng = rng();
+        let mut simulation = simulation(3, 3);
+        let events = simulation.step(0.01, &[occupant((1, 1), 1.)], &mut rng);
+
+        assert_eq!(events, vec![IceEvent::Cracked((1, 1))]);
+    }
+
+    #[test]
+    fn ice_cracks_once_stress_reaches_crack_delay() {
+        let mut rng = rng();
+        let mut simulation = simulation(3, 3);
+        simulation.config.crack_delay = 0.5;
+        simulation.set_thickness((1, 1), 3.);
+
+        assert!(simulation
+            .step(0.5, &[occupant((1, 1), 1.)], &mut rng)
+            .is_empty());
+        assert_eq!(simulation.state((1, 1)), SlotState::Ice { stress: 0.5 });
+        assert_eq!(
+            simulation.step(0.5, &[occupant((1, 1), 1.)], &mut rng),
+            vec![IceEvent::Cracked((1, 1))]
+        );
+    }
+
+    #[test]
+    fn loaded_cracks_break_after_break_delay_times_thickness_over_load() {
+        let mut simulation = simulation(3, 3);
+        simulation.config.break_delay = 0.5;
+        simulation.set_thickness((1, 1), 2.);
+        simulation.set_state(
+            (1, 1),
+            SlotState::Cracks {
+                since: 0.,
+                last_visited: 0.,
+            },
+        );
+        // two walkers share the slot, so it holds for 0.5 * 2 / 2 seconds
+        let walkers = [occupant((1, 1), 1.), occupant((1, 1), 1.)];
+        let mut rng = rng();
+
+        assert!(simulation.step(0.5, &walkers, &mut rng).is_empty());
+        assert_eq!(
+            simulation.step(0.25, &walkers, &mut rng),
+            vec![IceEvent::Broke((1, 1))]
+        );
+        assert!(simulation.is_water((1, 1)));
+    }
+
+    #[test]
+    fn slush_breaks_at_once() {
+        let mut simulation = simulation(3, 3);
+        simulation.set_thickness((1, 1), 5.);
+        simulation.set_terrain((1, 1), Terrain::Slush);
+        let events = simulation.step(0.01, &[occupant((1, 1), 1.)], &mut rng());
+
+        assert_eq!(events, vec![IceEvent::Broke((1, 1))]);
+    }
+
+    #[test]
+    fn unvisited_cracks_heal() {
+        let mut simulation = simulation(3, 3);
+        simulation.set_state(
+            (1, 1),
+            SlotState::Cracks {
+                since: 0.,
+                last_visited: 0.,
+            },
+        );
+        let mut rng = rng();
+
+        assert!(simulation.step(5., &[], &mut rng).is_empty());
+        assert_eq!(
+            simulation.step(5., &[], &mut rng),
+            vec![IceEvent::Healed((1, 1))]
+        );
+        assert_eq!(simulation.state((1, 1)), SlotState::Ice { stress: 0. });
+    }
+
+    #[test]
+    fn broken_ice_refreezes() {
+        let mut simulation = simulation(3, 3);
+        simulation.set_state((1, 1), SlotState::Brocken { since: 0. });
+        let mut rng = rng();
+
+        assert!(simulation.step(20., &[], &mut rng).is_empty());
+        assert_eq!(
+            simulation.step(10., &[], &mut rng),
+            vec![IceEvent::Refrozen((1, 1))]
+        );
+        assert!(!simulation.is_water((1, 1)));
+    }
+
+    #[test]
+    fn edges_crack_for_good() {
+        let mut rng = rng();
+        let mut simulation = simulation(4, 4);
+        simulation.crack_edges(1);
+        simulation.step(100., &[], &mut rng);
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let edge = x == 0 || y == 0 || x == 3 || y == 3;
+                assert_eq!(
+                    matches!(simulation.state((x, y)), SlotState::Cracks { .. }),
+                    edge
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn occupants_outside_the_grid_are_ignored() {
+        let mut rng = rng();
+        let mut simulation = simulation(3, 3);
+
+        assert!(simulation
+            .step(
+                0.01,
+                &[occupant((3, 1), 1.), occupant((1, 3), 1.)],
+                &mut rng
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn generated_thickness_stays_in_range() {
+        let mut rng = rng();
+        let mut simulation = simulation(20, 10);
+        simulation.generate_thickness(&mut rng, 0.5, 2.5, 4);
+
+        for y in 0..10 {
+            for x in 0..20 {
+                let thickness = simulation.thickness((x, y));
+                assert!((0.5..=2.5).contains(&thickness));
+            }
+        }
+    }
+
+    #[test]
+    fn cracks_spread_to_their_neighbours() {
+        let mut simulation = simulation(3, 3);
+        simulation.config.spread_chance = 1.;
+        simulation.set_state(
+            (1, 1),
+            SlotState::Cracks {
+                since: 0.,
+                last_visited: 0.,
+            },
+        );
+        let events = simulation.step(simulation.config.spread_interval, &[], &mut rng());
+
+        assert_eq!(
+            events,
+            vec![
+                IceEvent::Spread((1, 0)),
+                IceEvent::Spread((0, 1)),
+                IceEvent::Spread((2, 1)),
+                IceEvent::Spread((1, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn neighbouring_holes_share_a_water_region() {
+        let mut simulation = simulation(4, 3);
+        simulation.set_state((1, 1), SlotState::Brocken { since: 0. });
+        simulation.set_state((2, 1), SlotState::Brocken { since: 0. });
+        simulation.step(0.01, &[], &mut rng());
+
+        assert_eq!(simulation.water_regions().len(), 1);
+        assert_eq!(simulation.water_region((1, 1)), Some(0));
+        assert_eq!(simulation.water_region((2, 1)), Some(0));
+        assert_eq!(simulation.water_region((0, 1)), None);
+    }
+
+    #[test]
+    fn enclosed_ice_detaches_as_a_floe() {
+        let mut simulation = simulation(7, 7);
+        for slot in [(2, 3), (4, 3), (3, 2)] {
+            simulation.set_state(slot, SlotState::Brocken { since: 0. });
+        }
+        simulation.set_state((3, 4), SlotState::PERMANENT_CRACKS);
+        let events = simulation.step(0.01, &[occupant((3, 4), 1.)], &mut rng());
+
+        assert_eq!(
+            events,
+            vec![IceEvent::Broke((3, 4)), IceEvent::Detached(vec![(3, 3)])]
+        );
+        assert!(simulation.is_water((3, 3)));
+    }
+
+    #[test]
+    fn ice_next_to_rock_stays_attached() {
+        let mut simulation = simulation(7, 7);
+        for slot in [(2, 3), (4, 3), (3, 2), (2, 4), (4, 4)] {
+            simulation.set_state(slot, SlotState::Brocken { since: 0. });
+        }
+        simulation.set_state((3, 5), SlotState::Rock);
+        simulation.set_state((3, 6), SlotState::PERMANENT_CRACKS);
+        let events = simulation.step(0.01, &[occupant((3, 6), 1.)], &mut rng());
+
+        assert_eq!(events, vec![IceEvent::Broke((3, 6))]);
+        assert!(!simulation.is_water((3, 3)));
+    }
+
+    #[test]
+    fn shore_never_breaks() {
+        let mut simulation = simulation(3, 3);
+        simulation.config.crack_delay = 0.;
+        simulation.config.break_delay = 0.;
+        simulation.set_state((1, 1), SlotState::Shore);
+        let walker = [occupant((1, 1), 10.)];
+        let mut rng = rng();
+
+        assert!(simulation.step(1., &walker, &mut rng).is_empty());
+        assert!(simulation.step(1., &walker, &mut rng).is_empty());
+        assert_eq!(simulation.state((1, 1)), SlotState::Shore);
+    }
+
+    #[test]
+    fn ice_next_to_shore_stays_attached() {
+        let mut simulation = simulation(7, 7);
+        for slot in [(2, 3), (4, 3), (3, 2), (2, 4), (4, 4)] {
+            simulation.set_state(slot, SlotState::Brocken { since: 0. });
+        }
+        simulation.set_state((3, 5), SlotState::Shore);
+        simulation.set_state((3, 6), SlotState::PERMANENT_CRACKS);
+        let events = simulation.step(0.01, &[occupant((3, 6), 1.)], &mut rng());
+
+        assert_eq!(events, vec![IceEvent::Broke((3, 6))]);
+        assert!(!simulation.is_water((3, 3)));
+    }
+
+    #[test]
+    fn water_share_counts_water_under_a_footprint() {
+        let mut simulation = simulation(3, 3);
+        simulation.set_state((0, 0), SlotState::Brocken { since: 0. });
+        simulation.set_state((1, 0), SlotState::OpenWater);
+
+        assert_eq!(
+            simulation.water_share(&[(0, 0), (1, 0), (0, 1), (1, 1)]),
+            0.5
+        );
+        assert_eq!(simulation.water_share(&[(2, 2)]), 0.);
+        assert_eq!(simulation.water_share(&[]), 0.);
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::texture::ImageType;
+use bevy::utils::BoxedFuture;
+use serde::Deserialize;
+use std::path::Path;
+
+pub struct StampsPlugin;
+
+/// This plugin loads crack stamp libraries from `.stamps.ron` files
+/// A library lists the images to stamp together with how often each of them is picked.
+impl Plugin for StampsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<CrackStamps>()
+            .init_asset_loader::<CrackStampsLoader>();
+    }
+}
+
+#[derive(Clone, TypeUuid)]
+#[uuid = "0d3f6b9a-2c47-4e8e-b1a5-93c7d4e2f618"]
+pub struct CrackStamps {
+    pub stamps: Vec<CrackStamp>,
+    pub weights: Vec<f32>,
+}
+
+/// Pixels of one crack image, drawn pointing upwards
+#[derive(Clone)]
+pub struct CrackStamp {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl CrackStamp {
+    /// Radius of the circle covered by the stamp in any rotation
+    pub fn radius(&self) -> f32 {
+        ((self.width * self.width + self.height * self.height) as f32).sqrt() / 2.
+    }
+
+    /// The pixel at `offset` from the center of the stamp, with y growing upwards
+    pub fn sample(&self, offset: Vec2) -> Option<&[u8]> {
+        let column = (offset.x + self.width as f32 / 2.).floor();
+        let row = (self.height as f32 / 2. - offset.y).floor();
+        if column < 0. || row < 0. || column >= self.width as f32 || row >= self.height as f32 {
+            return None;
+        }
+        let pixel = (row as usize * self.width + column as usize) * 4;
+
+        Some(&self.data[pixel..pixel + 4])
+    }
+}
+
+#[derive(Deserialize)]
+struct StampsManifest {
+    stamps: Vec<StampEntry>,
+}
+
+#[derive(Deserialize)]
+struct StampEntry {
+    /// Path of the image relative to the assets folder
+    path: String,
+    #[serde(default = "default_weight")]
+    weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.
+}
+
+#[derive(Default)]
+struct CrackStampsLoader;
+
+impl AssetLoader for CrackStampsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let manifest: StampsManifest = ron::de::from_bytes(bytes)?;
+            if manifest.stamps.is_empty() {
+                anyhow::bail!("A crack stamp library needs at least one stamp");
+            }
+            let mut stamps = CrackStamps {
+                stamps: vec![],
+                weights: vec![],
+            };
+            for entry in manifest.stamps {
+                if entry.weight <= 0. {
+                    anyhow::bail!("Crack stamp {} needs a positive weight", entry.path);
+                }
+                let image_bytes = load_context.read_asset_bytes(&entry.path).await?;
+                let extension = Path::new(&entry.path)
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .unwrap_or("png");
+                let image = Image::from_buffer(&image_bytes, ImageType::Extension(extension))?;
+                let size = image.texture_descriptor.size;
+                let (width, height) = (size.width as usize, size.height as usize);
+                if image.data.len() != width * height * 4 {
+                    anyhow::bail!("Crack stamp {} has to be an RGBA image", entry.path);
+                }
+                stamps.stamps.push(CrackStamp {
+                    width,
+                    height,
+                    data: image.data,
+                });
+                stamps.weights.push(entry.weight);
+            }
+            load_context.set_default_asset(LoadedAsset::new(stamps));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stamps.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x2 pixels, each filled with its index
+    fn stamp() -> CrackStamp {
+        CrackStamp {
+            width: 2,
+            height: 2,
+            data: (0..4).flat_map(|pixel| [pixel; 4]).collect(),
+        }
+    }
+
+    #[test]
+    fn sample_measures_from_the_center_with_y_up() {
+        let stamp = stamp();
+
+        assert_eq!(stamp.sample(Vec2::new(-0.5, 0.5)), Some(&[0u8; 4][..]));
+        assert_eq!(stamp.sample(Vec2::new(0.5, 0.5)), Some(&[1u8; 4][..]));
+        assert_eq!(stamp.sample(Vec2::new(-0.5, -0.5)), Some(&[2u8; 4][..]));
+        assert_eq!(stamp.sample(Vec2::new(0.5, -0.5)), Some(&[3u8; 4][..]));
+    }
+
+    #[test]
+    fn sample_is_empty_outside_the_stamp() {
+        let stamp = stamp();
+
+        assert_eq!(stamp.sample(Vec2::new(1., 0.)), None);
+        assert_eq!(stamp.sample(Vec2::new(-1.5, 0.)), None);
+        assert_eq!(stamp.sample(Vec2::new(0., 1.5)), None);
+        assert_eq!(stamp.sample(Vec2::new(0., -1.)), None);
+    }
+
+    #[test]
+    fn radius_covers_the_corners() {
+        assert!((stamp().radius() - 2f32.sqrt()).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::ice::IceLabels;
+use crate::level::{Climate, CurrentLevel};
+use crate::loading::FontAssets;
+use crate::ui::GameStopWatch;
+use crate::{GameState, Level};
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+/// At this temperature the ice behaves like the defaults of [crate::simulation::IceConfig]
+const REFERENCE_DEGREES: f32 = -8.;
+/// Range shown by the thermometer
+const COLDEST_DEGREES: f32 = -15.;
+const WARMEST_DEGREES: f32 = 5.;
+
+pub struct TemperaturePlugin;
+
+/// This plugin follows the climate of the current level over the run
+/// Warmer ice cracks faster, breaks sooner and lets cracks spread further.
+impl Plugin for TemperaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Countdown).with_system(spawn_thermometer),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(
+                    update_temperature
+                        .label(TemperatureLabels::UpdateTemperature)
+                        .before(IceLabels::CheckIceGrid),
+                )
+                .with_system(update_thermometer.after(TemperatureLabels::UpdateTemperature)),
+        );
+    }
+}
+
+pub struct Temperature {
+    pub degrees: f32,
+}
+
+impl Temperature {
+    /// The warming trend plus a day and night cycle starting at midnight
+    pub fn at(climate: &Climate, seconds: f32) -> Self {
+        let day = (1. - (2. * PI * seconds / climate.day_length.max(1.)).cos()) / 2.;
+        Temperature {
+            degrees: climate.start + climate.warming * seconds / 60. + climate.day_swing * day,
+        }
+    }
+
+    /// Factor on how fast the ice gives in; 1 at the reference temperature
+    pub fn fragility(&self) -> f32 {
+        (1. + (self.degrees - REFERENCE_DEGREES) / 10.).clamp(0.25, 3.)
+    }
+}
+
+#[derive(Component)]
+struct ThermometerFill;
+
+#[derive(Component)]
+struct ThermometerText;
+
+fn spawn_thermometer(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    level: Res<CurrentLevel>,
+) {
+    let temperature = Temperature::at(&level.0.climate, 0.);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.0), Val::Px(40.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::SpaceEvenly,
+                align_items: AlignItems::Center,
+                position: Rect {
+                    right: Val::Px(10.),
+                    top: Val::Px(70.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: UiColor(Color::Rgba {
+                red: 0.7,
+                green: 0.7,
+                blue: 0.7,
+                alpha: 0.7,
+            }),
+            ..Default::default()
+        })
+        .insert(Level)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(50.0), Val::Px(14.0)),
+                        ..Default::default()
+                    },
+                    color: UiColor(Color::rgb_u8(34, 32, 52)),
+                    ..Default::default()
+                })
+                .with_children(|bar| {
+                    bar.spawn_bundle(NodeBundle {
+                        style: thermometer_style(&temperature),
+                        color: UiColor(thermometer_color(&temperature)),
+                        ..Default::default()
+                    })
+                    .insert(ThermometerFill);
+                });
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: thermometer_text(&temperature),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 24.0,
+                                color: Color::rgb_u8(34, 32, 52),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(ThermometerText);
+        });
+    commands.insert_resource(temperature);
+}
+
+/// Share of the thermometer range below the current temperature
+fn thermometer_level(temperature: &Temperature) -> f32 {
+    ((temperature.degrees - COLDEST_DEGREES) / (WARMEST_DEGREES - COLDEST_DEGREES)).clamp(0., 1.)
+}
+
+fn thermometer_style(temperature: &Temperature) -> Style {
+    Style {
+        size: Size::new(
+            Val::Percent(100. * thermometer_level(temperature)),
+            Val::Percent(100.),
+        ),
+        ..Default::default()
+    }
+}
+
+fn thermometer_color(temperature: &Temperature) -> Color {
+    let level = thermometer_level(temperature);
+    Color::rgb(0.2 + 0.7 * level, 0.4, 0.9 - 0.7 * level)
+}
+
+fn thermometer_text(temperature: &Temperature) -> String {
+    format!("{:.1}°C", temperature.degrees)
+}
+
+fn update_temperature(
+    level: Res<CurrentLevel>,
+    stop_watch: Res<GameStopWatch>,
+    mut temperature: ResMut<Temperature>,
+) {
+    *temperature = Temperature::at(&level.0.climate, stop_watch.0.elapsed_secs());
+}
+
+fn update_thermometer(
+    temperature: Res<Temperature>,
+    mut fill: Query<(&mut Style, &mut UiColor), With<ThermometerFill>>,
+    mut text: Query<&mut Text, With<ThermometerText>>,
+) {
+    for (mut style, mut color) in fill.iter_mut() {
+        *style = thermometer_style(&temperature);
+        *color = UiColor(thermometer_color(&temperature));
+    }
+    for mut text in text.iter_mut() {
+        text.sections[0].value = thermometer_text(&temperature);
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
+pub enum TemperatureLabels {
+    UpdateTemperature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f32, expected: f32) -> bool {
+        (actual - expected).abs() < 1e-4
+    }
+
+    #[test]
+    fn days_warm_up_and_cool_down_on_top_of_the_trend() {
+        let climate = Climate::default();
+
+        assert!(close(Temperature::at(&climate, 0.).degrees, -8.));
+        // midday: the trend adds 1.125 degrees and the day the full swing
+        assert!(close(Temperature::at(&climate, 45.).degrees, -2.875));
+        assert!(close(Temperature::at(&climate, 90.).degrees, -5.75));
+    }
+
+    #[test]
+    fn fragility_grows_with_the_temperature_within_bounds() {
+        let fragility = |degrees| Temperature { degrees }.fragility();
+
+        assert!(close(fragility(REFERENCE_DEGREES), 1.));
+        assert!(close(fragility(2.), 2.));
+        assert!(close(fragility(-40.), 0.25));
+        assert!(close(fragility(40.), 3.));
+    }
+}
@@ -1,8 +1,14 @@
+use crate::camera::ScreenAnchor;
+use crate::climb::ClimbLabels;
 use crate::countdown::CountdownTimer;
+use crate::gamepad::GamepadLabels;
 use crate::ice::IceLabels;
+use crate::level::{CurrentLevel, Goal};
 use crate::loading::{FontAssets, TextureAssets};
-use crate::menu::ButtonColors;
-use crate::player::{Drowning, Player, PlayerFallEvent};
+use crate::menu::{seed_text, ButtonColors};
+use crate::player::{Drowning, Player, PlayerDrownEvent, PlayerLabels, Stamina};
+use crate::rng::GameSeed;
+use crate::shore::{PlayerSafeEvent, Rescues, ShoreLabels};
 use crate::{GameState, Level};
 use bevy::core::Stopwatch;
 use bevy::prelude::*;
@@ -18,6 +24,7 @@ impl Plugin for UiPlugin {
             .add_system_set(
                 SystemSet::on_enter(GameState::Countdown)
                     .with_system(spawn_timer)
+                    .with_system(spawn_stamina_bar)
                     .with_system(spawn_countdown),
             )
             .add_system_set(
@@ -32,9 +39,15 @@ impl Plugin for UiPlugin {
                             .after(IceLabels::BreakIce),
                     )
                     .with_system(update_high_score.after(UiLabels::UpdateTimer))
-                    .with_system(player_fall.after(IceLabels::CheckIceGrid))
+                    .with_system(update_stamina_bar.after(PlayerLabels::SetGait))
+                    .with_system(player_drown.after(ClimbLabels::Struggle))
+                    .with_system(player_safe.after(ShoreLabels::ReachShore))
                     .with_system(spawn_restart_button.after(IceLabels::CheckIceGrid))
-                    .with_system(click_restart_button.after(IceLabels::CheckIceGrid)),
+                    .with_system(
+                        click_restart_button
+                            .after(IceLabels::CheckIceGrid)
+                            .after(GamepadLabels::PressButtons),
+                    ),
             );
     }
 }
@@ -133,10 +146,76 @@ fn spawn_high_score(mut commands: Commands, font_assets: Res<FontAssets>) {
 #[derive(Component)]
 struct TimerText;
 
+#[derive(Component)]
+struct StaminaFill;
+
+fn spawn_stamina_bar(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.0), Val::Px(20.0)),
+                position_type: PositionType::Absolute,
+                padding: Rect::all(Val::Px(4.)),
+                position: Rect {
+                    right: Val::Px(10.),
+                    top: Val::Px(120.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            color: UiColor(Color::Rgba {
+                red: 0.7,
+                green: 0.7,
+                blue: 0.7,
+                alpha: 0.7,
+            }),
+            ..Default::default()
+        })
+        .insert(Level)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: stamina_style(&Stamina::default()),
+                    color: UiColor(stamina_color(&Stamina::default())),
+                    ..Default::default()
+                })
+                .insert(StaminaFill);
+        });
+}
+
+fn stamina_style(stamina: &Stamina) -> Style {
+    Style {
+        size: Size::new(Val::Percent(100. * stamina.value), Val::Percent(100.)),
+        ..Default::default()
+    }
+}
+
+/// Grey while exhausted, so it is clear why tiptoeing and sprinting stopped working
+fn stamina_color(stamina: &Stamina) -> Color {
+    if stamina.exhausted {
+        Color::rgb(0.45, 0.45, 0.45)
+    } else {
+        Color::rgb(0.3, 0.75, 0.35)
+    }
+}
+
+fn update_stamina_bar(
+    stamina: Res<Stamina>,
+    mut fill: Query<(&mut Style, &mut UiColor), With<StaminaFill>>,
+) {
+    if !stamina.is_changed() {
+        return;
+    }
+    for (mut style, mut color) in fill.iter_mut() {
+        *style = stamina_style(&stamina);
+        *color = UiColor(stamina_color(&stamina));
+    }
+}
+
 #[derive(Component)]
 struct HighScoreText;
 
-struct GameStopWatch(Stopwatch);
+pub struct GameStopWatch(pub Stopwatch);
 
 impl Default for GameStopWatch {
     fn default() -> Self {
@@ -149,11 +228,13 @@ fn update_timer(
     mut game_stop_watch: ResMut<GameStopWatch>,
     mut timer_text: Query<&mut Text, With<TimerText>>,
     mut high_score: ResMut<HighScore>,
+    level: Res<CurrentLevel>,
 ) {
     game_stop_watch.0.tick(time.delta());
     let score = game_stop_watch.0.elapsed_secs();
     timer_text.single_mut().sections[0].value = format!("{:.2}", score);
-    if score > high_score.0 {
+    // when there is a shore to reach, only banked scores count
+    if level.0.goal == Goal::Survive && score > high_score.0 {
         high_score.0 = score;
     }
 }
@@ -176,11 +257,13 @@ impl Default for RestartTimer {
     }
 }
 
-fn player_fall(
+fn player_drown(
     mut commands: Commands,
-    mut events: EventReader<PlayerFallEvent>,
+    mut events: EventReader<PlayerDrownEvent>,
     mut game_stop_watch: ResMut<GameStopWatch>,
     textures: Res<TextureAssets>,
+    font_assets: Res<FontAssets>,
+    seed: Res<GameSeed>,
     player: Query<Entity, With<Player>>,
 ) {
     for _ in events.iter() {
@@ -193,10 +276,97 @@ fn player_fall(
                 transform: Transform::from_xyz(0., 0., END_Z),
                 ..SpriteBundle::default()
             })
+            .insert(ScreenAnchor(Vec2::ZERO))
             .insert(Level);
+        spawn_seed_text(&mut commands, &font_assets, &seed);
+    }
+}
+
+fn player_safe(
+    mut commands: Commands,
+    mut events: EventReader<PlayerSafeEvent>,
+    mut game_stop_watch: ResMut<GameStopWatch>,
+    mut high_score: ResMut<HighScore>,
+    rescues: Res<Rescues>,
+    font_assets: Res<FontAssets>,
+    seed: Res<GameSeed>,
+) {
+    for _ in events.iter() {
+        game_stop_watch.0.pause();
+        let score = game_stop_watch.0.elapsed_secs();
+        if score > high_score.0 {
+            high_score.0 = score;
+        }
+        commands.insert_resource(RestartTimer::default());
+        spawn_seed_text(&mut commands, &font_assets, &seed);
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Px(60.)),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    position: Rect {
+                        top: Val::Percent(25.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                color: UiColor(Color::NONE),
+                ..Default::default()
+            })
+            .insert(Level)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: format!(
+                                "Safe ashore after {:.2}s with {} rescued!",
+                                score, rescues.0
+                            ),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 40.0,
+                                color: Color::rgb_u8(34, 32, 52),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                });
+            });
     }
 }
 
+/// Shows which seed the run used, so it can be typed in the menu to play it again
+fn spawn_seed_text(commands: &mut Commands, font_assets: &FontAssets, seed: &GameSeed) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![TextSection {
+                    value: seed_text(seed.value),
+                    style: TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 20.0,
+                        color: Color::rgb_u8(34, 32, 52),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            ..Default::default()
+        })
+        .insert(Level);
+}
+
 fn spawn_restart_button(
     mut commands: Commands,
     time: Res<Time>,
@@ -282,6 +452,7 @@ fn spawn_countdown(mut commands: Commands, textures: Res<TextureAssets>) {
             transform,
             ..SpriteSheetBundle::default()
         })
+        .insert(ScreenAnchor(Vec2::new(0., 200.)))
         .insert(Countdown);
 }
 